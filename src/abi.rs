@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use ethers::types::U256;
+use ethers::utils::keccak256;
+use eyre::Result;
+use json::JsonValue;
+use log::warn;
+
+use crate::model::Event;
+use crate::{query_class_by_hash, query_class_hash_at};
+
+/// Entrypoints and events common to most contracts, named without any lookup.
+pub const WELL_KNOWN_NAMES: [&str; 14] = [
+    "__execute__",
+    "__validate__",
+    "__validate_declare__",
+    "__validate_deploy__",
+    "__default__",
+    "__l1_default__",
+    "constructor",
+    "transfer",
+    "transferFrom",
+    "transfer_from",
+    "approve",
+    "Transfer",
+    "Approval",
+    "Upgraded",
+];
+
+/// Computes the StarkNet keccak of some data: its keccak256, keeping the 250 low bits.
+pub fn starknet_keccak(data: &[u8]) -> U256 {
+    let mask = (U256::one() << 250) - 1;
+    U256::from_big_endian(&keccak256(data)) & mask
+}
+
+/// Computes the selector of an entrypoint or an event.
+///
+/// # Example
+///
+/// ```
+/// use howmuch_rs::abi::selector;
+///
+/// assert_eq!(
+///     format!("{:#x}", selector("Transfer")),
+///     "0x99cd8bde557814842a3121e8ddfd433a539b8c9f14bf31ebf108d12e6196e9"
+/// );
+/// ```
+pub fn selector(name: &str) -> U256 {
+    starknet_keccak(name.as_bytes())
+}
+
+/// The names of the selectors found in ABIs.
+#[derive(Debug, Clone, Default)]
+pub struct SelectorNames {
+    names: HashMap<U256, String>,
+}
+
+impl SelectorNames {
+    /// Names the selectors of [`WELL_KNOWN_NAMES`].
+    pub fn well_known() -> Self {
+        let mut names = Self::default();
+        for name in WELL_KNOWN_NAMES {
+            names.insert(name);
+        }
+        names
+    }
+
+    /// Names the selector of an entrypoint or event.
+    /// Cairo 1 event paths such as `openzeppelin::token::erc20::ERC20::Transfer` are named by their last segment,
+    /// which their selector is computed from.
+    pub fn insert(&mut self, name: &str) {
+        let name = name.rsplit("::").next().unwrap_or(name);
+        self.names.insert(selector(name), name.to_string());
+    }
+
+    /// Returns the name of a selector, if known.
+    ///
+    /// # Arguments
+    ///
+    /// * `selector` - The selector, as a `0x` prefixed hexadecimal string.
+    pub fn name(&self, selector: &str) -> Option<&str> {
+        let selector = U256::from_str(selector).ok()?;
+        self.names.get(&selector).map(|name| name.as_str())
+    }
+
+    /// Names the entrypoints and events of an ABI, either Cairo 0 or Cairo 1.
+    pub fn extend_from_abi(&mut self, abi: &JsonValue) {
+        for item in abi.members() {
+            if let (Some("function" | "l1_handler" | "constructor" | "event"), Some(name)) =
+                (item["type"].as_str(), item["name"].as_str())
+            {
+                self.insert(name);
+            }
+            // Cairo 1 interfaces and enum events nest their items.
+            self.extend_from_abi(&item["items"]);
+            self.extend_from_abi(&item["variants"]);
+        }
+    }
+
+    /// Names the entrypoints and events of a contract class, or of a bare ABI.
+    /// Sierra classes serialize their ABI as a string, which is parsed as well.
+    pub fn extend_from_class(&mut self, class: &str) -> Result<()> {
+        let j = json::parse(class)?;
+        let abi = match &j {
+            JsonValue::Array(_) => j.clone(),
+            _ => match j["abi"].as_str() {
+                Some(abi) => json::parse(abi)?,
+                None => j["abi"].clone(),
+            },
+        };
+        if !abi.is_array() {
+            return Err(eyre::eyre!("No ABI found in the contract class"));
+        }
+        self.extend_from_abi(&abi);
+        Ok(())
+    }
+
+    /// Names the entrypoints and events of a contract class or ABI file.
+    pub fn extend_from_file(&mut self, filename: &str) -> Result<()> {
+        self.extend_from_class(&std::fs::read_to_string(filename)?)
+    }
+
+    /// Names the well known selectors, then the entrypoints and events of each contract class or ABI file.
+    pub fn try_from_files(filenames: &[String]) -> Result<Self> {
+        let mut names = Self::well_known();
+        for filename in filenames {
+            names.extend_from_file(filename)?;
+        }
+        Ok(names)
+    }
+
+    /// Same as [`SelectorNames::extend_from_contract`] for several contracts.
+    /// Contracts whose class can't be queried are skipped with a warning.
+    pub fn extend_from_contracts(
+        &mut self,
+        contracts: &[(&str, Option<&str>)],
+        network_gateway_url: &str,
+    ) {
+        for (contract_address, class_hash) in contracts {
+            if let Err(err) =
+                self.extend_from_contract(contract_address, *class_hash, network_gateway_url)
+            {
+                warn!("Could not query the class of {contract_address}: {err}");
+            }
+        }
+    }
+
+    /// Names the entrypoints and events of a deployed contract, querying its class.
+    ///
+    /// # Arguments
+    ///
+    /// * `contract_address` - The contract address.
+    /// * `class_hash` - The contract class hash, queried from the contract address if not provided.
+    /// * `network_gateway_url` - The network gateway URL.
+    pub fn extend_from_contract(
+        &mut self,
+        contract_address: &str,
+        class_hash: Option<&str>,
+        network_gateway_url: &str,
+    ) -> Result<()> {
+        let class_hash = match class_hash {
+            Some(class_hash) => class_hash.to_string(),
+            None => query_class_hash_at(contract_address, network_gateway_url)?,
+        };
+        self.extend_from_class(&query_class_by_hash(&class_hash, network_gateway_url)?)
+    }
+}
+
+/// Format an event, naming its first key when possible.
+///
+/// # Example
+///
+/// ```
+/// use howmuch_rs::abi::{format_event, SelectorNames};
+/// use howmuch_rs::model::TransactionReceipt;
+///
+/// let tx_receipt = TransactionReceipt::try_from_file("tests/example.json").unwrap();
+/// let events = tx_receipt.events().unwrap();
+/// assert_eq!(
+///     format_event(&events[1], &SelectorNames::well_known()),
+///     "0x49d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7::Transfer(0x77d5345916a48b88ad002ead40344edbaa3a900d66c40d0b8e0c4c9f529d9a6, 0x46a89ae102987331d369645031b49c27738ed096f2789c24449966da4c6de6b, 0xbcea9f8f91dd, 0x0)"
+/// );
+/// ```
+pub fn format_event(event: &Event, names: &SelectorNames) -> String {
+    let (name, keys) = match event.keys.split_first() {
+        Some((selector, keys)) => (names.name(selector).unwrap_or(selector), keys),
+        None => ("<anonymous>", &event.keys[..]),
+    };
+    let arguments: Vec<&str> = keys
+        .iter()
+        .chain(event.data.iter())
+        .map(|x| x.as_str())
+        .collect();
+    format!("{}::{name}({})", event.from_address, arguments.join(", "))
+}
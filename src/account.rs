@@ -0,0 +1,253 @@
+use std::str::FromStr;
+
+use ethers::types::U256;
+use tabled::{Alignment, Style, Table, Tabled};
+
+use crate::resources::{CairoResources, Resource, Weights};
+use crate::trace::{CallPhase, CallTree};
+
+/// The name of the accounts whose class is unknown.
+pub const UNKNOWN_ACCOUNT: &str = "unknown";
+
+/// The classes of the widespread account implementations, named by their vendor.
+/// Cairo 0 accounts are deployed behind a proxy, whose class is the one found in traces.
+pub const KNOWN_ACCOUNT_CLASSES: [(&str, &str); 9] = [
+    (
+        "openzeppelin",
+        "0x4d07e40e93398ed3c76981e72dd1fd22557a78ce36c0515f679e27f0bb5bc5f",
+    ),
+    (
+        "openzeppelin",
+        "0x61dac032f228abef9c6626f995015233097ae253a7f72d68552db02f2971b8f",
+    ),
+    (
+        "argent",
+        "0x25ec026985a3bf9d0cc1fe17326b245dfdc3ff89b8fde106542a3ea56c5a918",
+    ),
+    (
+        "argent",
+        "0x1a736d6ed154502257f02b1ccdf4d9d1089f80811cd6acad48e6b6a9d1f2003",
+    ),
+    (
+        "argent",
+        "0x29927c8af6bccf3f6fda035981e765a7bdbf18a2dc0d630494f8758aa908e2b",
+    ),
+    (
+        "argent",
+        "0x36078334509b514626504edc9fb252328d1a240e4e948bef8d0c08dff45927f",
+    ),
+    (
+        "braavos",
+        "0x3131fa018d520a037686ce3efddeab8f28895662f019ca3ca18a626650f7d1e",
+    ),
+    (
+        "braavos",
+        "0x816dd0297efc55dc1e7559020a3a825e81ef734b558f03c83325d4da7e6253",
+    ),
+    (
+        "braavos",
+        "0x13bfe114fb1cf405bfc3a7f8dbe2d91db146c17521d40dcf57e16d6b59fa8e6",
+    ),
+];
+
+/// A name for the accounts of a class, such as `argent=0x1a73...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountLabel {
+    pub name: String,
+    pub class_hash: U256,
+}
+
+impl FromStr for AccountLabel {
+    type Err = String;
+
+    /// Parse an account label, written `<name>=<class hash>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, class_hash) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid account label `{s}`, expected <name>=<class hash>"))?;
+        let class_hash = U256::from_str(class_hash.trim())
+            .map_err(|e| format!("Invalid class hash `{}`: {e}", class_hash.trim()))?;
+        Ok(Self {
+            name: name.trim().to_string(),
+            class_hash,
+        })
+    }
+}
+
+/// The cost of each phase of the transactions sent by the accounts of one class.
+/// The gas of a phase is its amount of the resource limiting the transaction, times its weight,
+/// so that the gas of the phases adds up to the limiting factor of the transaction.
+#[derive(Debug, Clone)]
+pub struct AccountOverhead {
+    /// The account label, or its class hash if it has none.
+    pub account: String,
+    pub transactions: usize,
+    /// The resources used to validate the transactions, summed over the transactions.
+    pub validate: CairoResources,
+    /// The resources used to transfer the fees, summed over the transactions.
+    pub fee_transfer: CairoResources,
+    pub validate_gas: f32,
+    pub execute_gas: f32,
+    pub fee_transfer_gas: f32,
+}
+
+impl AccountOverhead {
+    fn new(account: &str) -> Self {
+        Self {
+            account: account.to_string(),
+            transactions: 0,
+            validate: CairoResources::new("validate", 0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+            fee_transfer: CairoResources::new("fee_transfer", 0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+            validate_gas: 0.0,
+            execute_gas: 0.0,
+            fee_transfer_gas: 0.0,
+        }
+    }
+
+    /// Returns the validation and fee transfer gas, per transaction.
+    pub fn overhead_per_transaction(&self) -> f32 {
+        match self.transactions {
+            0 => 0.0,
+            n => (self.validate_gas + self.fee_transfer_gas) / n as f32,
+        }
+    }
+
+    /// Returns the share of the gas spent on validation and fee transfer, rather than on the calls.
+    pub fn overhead_share(&self) -> f32 {
+        let total = self.validate_gas + self.execute_gas + self.fee_transfer_gas;
+        if total > 0.0 {
+            (self.validate_gas + self.fee_transfer_gas) / total
+        } else {
+            0.0
+        }
+    }
+}
+
+impl AccountLabel {
+    /// Returns the labels of [`KNOWN_ACCOUNT_CLASSES`].
+    pub fn known() -> Vec<Self> {
+        KNOWN_ACCOUNT_CLASSES
+            .iter()
+            .filter_map(|(name, class_hash)| Self::from_str(&format!("{name}={class_hash}")).ok())
+            .collect()
+    }
+}
+
+/// Returns the label of an account class, or the class hash itself if it has none.
+/// The labels provided take precedence over the known account classes.
+fn account_name(class_hash: Option<&str>, labels: &[AccountLabel]) -> String {
+    let class_hash = match class_hash {
+        Some(class_hash) => class_hash,
+        None => return UNKNOWN_ACCOUNT.to_string(),
+    };
+    let known = AccountLabel::known();
+    let label = U256::from_str(class_hash).ok().and_then(|hash| {
+        labels
+            .iter()
+            .chain(known.iter())
+            .find(|label| label.class_hash == hash)
+    });
+    match label {
+        Some(label) => label.name.clone(),
+        None => class_hash.to_string(),
+    }
+}
+
+/// Group transactions per account class, and sum the cost of their phases.
+///
+/// # Arguments
+///
+/// * `call_trees` - The call trees of the transactions.
+/// * `labels` - Names for account classes, on top of [`KNOWN_ACCOUNT_CLASSES`].
+/// * `weights` - The weight of each resource.
+///
+/// # Returns
+///
+/// The overhead of each account class, in order of first appearance.
+/// Transactions without validation, sent before account abstraction, are skipped.
+pub fn account_overheads(
+    call_trees: &[CallTree],
+    labels: &[AccountLabel],
+    weights: &Weights,
+) -> Vec<AccountOverhead> {
+    let mut overheads: Vec<AccountOverhead> = vec![];
+    for call_tree in call_trees {
+        let validate = match call_tree.invocation(CallPhase::Validate) {
+            Some(validate) => validate,
+            None => continue,
+        };
+        let account = account_name(validate.class_hash.as_deref(), labels);
+        let index = match overheads.iter().position(|o| o.account == account) {
+            Some(index) => index,
+            None => {
+                overheads.push(AccountOverhead::new(&account));
+                overheads.len() - 1
+            }
+        };
+        let overhead = &mut overheads[index];
+
+        let (limiting_resource, _) = call_tree.total_resources().limiting_factor(weights);
+        let gas = |phase: CallPhase| {
+            call_tree
+                .invocation(phase)
+                .map(|call| call.resources.get(limiting_resource) * weights.get(limiting_resource))
+                .unwrap_or(0.0)
+        };
+        overhead.transactions += 1;
+        overhead.validate_gas += gas(CallPhase::Validate);
+        overhead.execute_gas += gas(CallPhase::Execute);
+        overhead.fee_transfer_gas += gas(CallPhase::FeeTransfer);
+        for resource in Resource::ALL {
+            overhead.validate.set(
+                resource,
+                overhead.validate.get(resource) + validate.resources.get(resource),
+            );
+            if let Some(fee_transfer) = call_tree.invocation(CallPhase::FeeTransfer) {
+                overhead.fee_transfer.set(
+                    resource,
+                    overhead.fee_transfer.get(resource) + fee_transfer.resources.get(resource),
+                );
+            }
+        }
+    }
+    overheads
+}
+
+#[derive(Tabled)]
+struct OverheadRow {
+    account: String,
+    transactions: usize,
+    #[tabled(rename = "validate steps")]
+    validate_steps: f32,
+    #[tabled(rename = "validate gas")]
+    validate_gas: f32,
+    #[tabled(rename = "execute gas")]
+    execute_gas: f32,
+    #[tabled(rename = "fee transfer gas")]
+    fee_transfer_gas: f32,
+    #[tabled(rename = "overhead gas")]
+    overhead_gas: f32,
+    #[tabled(rename = "overhead share")]
+    overhead_share: String,
+}
+
+/// Render the overheads as a table, averaged per transaction.
+pub fn overheads_to_table(overheads: &[AccountOverhead]) -> String {
+    let rows = overheads.iter().map(|overhead| {
+        let n = overhead.transactions.max(1) as f32;
+        OverheadRow {
+            account: overhead.account.clone(),
+            transactions: overhead.transactions,
+            validate_steps: overhead.validate.steps / n,
+            validate_gas: overhead.validate_gas / n,
+            execute_gas: overhead.execute_gas / n,
+            fee_transfer_gas: overhead.fee_transfer_gas / n,
+            overhead_gas: overhead.overhead_per_transaction(),
+            overhead_share: format!("{:.1}%", overhead.overhead_share() * 100.0),
+        }
+    });
+
+    let mut table = Table::new(rows);
+    table.with(Alignment::center()).with(Style::modern());
+    table.to_string()
+}
@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use ethers::types::U256;
+use ethers::utils;
+use eyre::Result;
+use log::debug;
+use tabled::{Alignment, Panel, Style, Table, Tabled};
+
+use crate::currencies::get_historical_token_price;
+use crate::date::{format_date, SECONDS_PER_DAY};
+use crate::model::{same_address, FeeUnit};
+use crate::query_block;
+use crate::units::{format_amount, Unit};
+
+/// Output format of a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+/// A transaction sent by an account, and what it paid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountTransaction {
+    pub transaction_hash: String,
+    pub block_number: u64,
+    /// The timestamp of the block which included the transaction.
+    pub timestamp: u64,
+    pub actual_fee: U256,
+    pub fee_unit: FeeUnit,
+    /// The USD price of the fee token on the day the transaction was included.
+    pub token_price: Option<f32>,
+}
+
+impl AccountTransaction {
+    /// Returns the actual fee in the fee token, e.g. in ETH rather than in wei.
+    pub fn fee_in_token(&self) -> Result<String> {
+        Ok(utils::format_units(self.actual_fee, "ether")?)
+    }
+
+    /// Returns the actual fee in a unit, or in the fee token if none is given.
+    /// Fees paid in the other token than the unit's are kept in their token.
+    pub fn fee_in(&self, unit: Option<Unit>) -> Result<String> {
+        let unit = match unit {
+            Some(unit)
+                if unit
+                    .fee_unit()
+                    .is_none_or(|fee_unit| fee_unit == self.fee_unit) =>
+            {
+                unit
+            }
+            _ => Unit::token(self.fee_unit),
+        };
+        format_amount(self.actual_fee, self.fee_unit, unit, self.token_price)
+    }
+
+    /// Returns the value of the actual fee in USD, on the day the transaction was included.
+    pub fn fee_in_usd(&self) -> Option<f64> {
+        let fee_in_token: f64 = self.fee_in_token().ok()?.parse().ok()?;
+        Some(fee_in_token * f64::from(self.token_price?))
+    }
+}
+
+/// Find the first block produced at or after a timestamp, by bisecting the chain.
+/// # Arguments
+/// * `timestamp` - The Unix timestamp.
+/// * `network_gateway_url` - The network gateway URL.
+/// # Returns
+/// The block number, or the latest block if the timestamp is in the future.
+pub fn find_block_at(timestamp: u64, network_gateway_url: &str) -> Result<u64> {
+    let latest = query_block("latest", network_gateway_url)?;
+    if latest.timestamp()? < timestamp {
+        return latest.block_number();
+    }
+    bisect_block(timestamp, latest.block_number()?, |number| {
+        debug!("querying block {} to find timestamp {}", number, timestamp);
+        query_block(&number.to_string(), network_gateway_url)?.timestamp()
+    })
+}
+
+/// Returns the first block, up to `latest`, whose timestamp is at or after `timestamp`.
+fn bisect_block(
+    timestamp: u64,
+    latest: u64,
+    mut timestamp_of: impl FnMut(u64) -> Result<u64>,
+) -> Result<u64> {
+    let (mut low, mut high) = (0, latest);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if timestamp_of(middle)? < timestamp {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    Ok(low)
+}
+
+/// Find the blocks produced over a range of UTC days.
+/// # Arguments
+/// * `from_date` - The timestamp of the first day of the range.
+/// * `to_date` - The timestamp of the last day of the range, included.
+/// * `network_gateway_url` - The network gateway URL.
+/// # Returns
+/// The first and last blocks of the range.
+pub fn find_block_range(
+    from_date: u64,
+    to_date: u64,
+    network_gateway_url: &str,
+) -> Result<(u64, u64)> {
+    let end = to_date + SECONDS_PER_DAY;
+    let from = find_block_at(from_date, network_gateway_url)?;
+    let to = find_block_at(end, network_gateway_url)?;
+    // Unless the range ends in the future, the block found is the first one of the next day.
+    let to = if query_block(&to.to_string(), network_gateway_url)?.timestamp()? >= end {
+        to.saturating_sub(1)
+    } else {
+        to
+    };
+    Ok((from, to))
+}
+
+/// Maximum number of blocks scanned for an account fee history, each one being a gateway query.
+pub const MAX_HISTORY_BLOCKS: u64 = 10_000;
+
+/// Query the transactions an account sent over a range of blocks, scanning every block.
+/// The feeder gateway can't filter the transactions of an account, so ranges are capped
+/// at [`MAX_HISTORY_BLOCKS`] blocks.
+/// # Arguments
+/// * `account` - The account address.
+/// * `from` - The first block of the range.
+/// * `to` - The last block of the range, included.
+/// * `network_gateway_url` - The network gateway URL.
+/// * `with_prices` - Whether to query the historical price of the fee token, once per day.
+/// # Returns
+/// The transactions, from the oldest to the most recent one.
+pub fn query_account_fee_history(
+    account: &str,
+    from: u64,
+    to: u64,
+    network_gateway_url: &str,
+    with_prices: bool,
+) -> Result<Vec<AccountTransaction>> {
+    if from > to {
+        return Err(eyre::eyre!("Invalid block range: {} > {}", from, to));
+    }
+    if to - from >= MAX_HISTORY_BLOCKS {
+        return Err(eyre::eyre!(
+            "Block range {}-{} spans {} blocks, over the maximum of {}: split it in smaller ranges",
+            from,
+            to,
+            to - from + 1,
+            MAX_HISTORY_BLOCKS
+        ));
+    }
+    let mut token_prices: HashMap<(FeeUnit, String), Option<f32>> = HashMap::new();
+    let mut history = vec![];
+    for number in from..=to {
+        debug!(
+            "querying block {} for the transactions of {}",
+            number, account
+        );
+        let block = query_block(&number.to_string(), network_gateway_url)?;
+        let timestamp = block.timestamp()?;
+        let transactions = block.transactions()?;
+        let tx_receipts = block.transaction_receipts()?;
+        for (tx, tx_receipt) in transactions.iter().zip(tx_receipts.iter()) {
+            match tx.sender_address()? {
+                Some(sender) if same_address(&sender, account) => {}
+                _ => continue,
+            }
+            let fee_unit = tx_receipt.fee_unit()?;
+            let token_price = if with_prices {
+                *token_prices
+                    .entry((fee_unit, format_date(timestamp)))
+                    .or_insert_with(|| get_historical_token_price(fee_unit, timestamp))
+            } else {
+                None
+            };
+
+            history.push(AccountTransaction {
+                transaction_hash: tx_receipt.transaction_hash()?,
+                block_number: number,
+                timestamp,
+                actual_fee: tx_receipt.actual_fee()?,
+                fee_unit,
+                token_price,
+            });
+        }
+    }
+    Ok(history)
+}
+
+/// Formats an account fee history as CSV, for accounting.
+/// The `fee` column is in `unit`, see [`AccountTransaction::fee_in`].
+pub fn fee_history_to_csv(history: &[AccountTransaction], unit: Option<Unit>) -> Result<String> {
+    let mut csv = String::from(
+        "transaction_hash,block_number,timestamp,date,actual_fee,fee_unit,fee_token,\
+         fee_in_token,token_price_usd,fee_usd,fee\n",
+    );
+    for tx in history {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{}\n",
+            tx.transaction_hash,
+            tx.block_number,
+            tx.timestamp,
+            format_date(tx.timestamp),
+            tx.actual_fee,
+            tx.fee_unit.name(),
+            tx.fee_unit.token_symbol(),
+            tx.fee_in_token()?,
+            tx.token_price.map(|p| p.to_string()).unwrap_or_default(),
+            tx.fee_in_usd()
+                .map(|usd| usd.to_string())
+                .unwrap_or_default(),
+            tx.fee_in(unit)?,
+        ));
+    }
+    Ok(csv)
+}
+
+/// Formats an account fee history as JSON.
+/// The `fee` field is in `unit`, see [`AccountTransaction::fee_in`].
+pub fn fee_history_to_json(history: &[AccountTransaction], unit: Option<Unit>) -> Result<String> {
+    let rows = history
+        .iter()
+        .map(|tx| {
+            Ok(serde_json::json!({
+                "transaction_hash": tx.transaction_hash,
+                "block_number": tx.block_number,
+                "timestamp": tx.timestamp,
+                "date": format_date(tx.timestamp),
+                "actual_fee": tx.actual_fee.to_string(),
+                "fee_unit": tx.fee_unit.name(),
+                "fee_token": tx.fee_unit.token_symbol(),
+                "fee_in_token": tx.fee_in_token()?,
+                "token_price_usd": tx.token_price,
+                "fee_usd": tx.fee_in_usd(),
+                "fee": tx.fee_in(unit)?,
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(serde_json::to_string_pretty(&rows)?)
+}
+
+#[derive(Tabled)]
+struct AccountTransactionRow {
+    date: String,
+    block: u64,
+    #[tabled(rename = "transaction hash")]
+    transaction_hash: String,
+    fee: String,
+    #[tabled(rename = "fee (USD)")]
+    fee_usd: String,
+}
+
+/// Renders an account fee history as a table, followed by the total fees per token.
+/// The fees are in `unit`, see [`AccountTransaction::fee_in`].
+pub fn fee_history_to_table(history: &[AccountTransaction], unit: Option<Unit>) -> Result<String> {
+    let rows = history
+        .iter()
+        .map(|tx| {
+            Ok(AccountTransactionRow {
+                date: format_date(tx.timestamp),
+                block: tx.block_number,
+                transaction_hash: tx.transaction_hash.clone(),
+                fee: tx.fee_in(unit)?,
+                fee_usd: tx
+                    .fee_in_usd()
+                    .map(|usd| format!("${usd:.4}"))
+                    .unwrap_or_else(|| String::from("-")),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut totals = vec![format!("{} transactions", history.len())];
+    for fee_unit in [FeeUnit::Wei, FeeUnit::Fri] {
+        let txs: Vec<&AccountTransaction> = history
+            .iter()
+            .filter(|tx| tx.fee_unit == fee_unit)
+            .collect();
+        if txs.is_empty() {
+            continue;
+        }
+        let total = txs.iter().fold(U256::zero(), |total, tx| {
+            total.saturating_add(tx.actual_fee)
+        });
+        let total_usd: f64 = txs.iter().filter_map(|tx| tx.fee_in_usd()).sum();
+        totals.push(format!(
+            "Total {}: {} {} (${total_usd:.4} USD)",
+            fee_unit.token_symbol(),
+            utils::format_units(total, "ether")?,
+            fee_unit.token_symbol()
+        ));
+    }
+
+    let mut table = Table::new(rows);
+    table
+        .with(Panel::footer(totals.join("\n")))
+        .with(Alignment::center())
+        .with(Style::modern());
+    Ok(table.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bisect_block_finds_the_first_block_at_a_timestamp() {
+        // One block every 10 seconds, from 1000.
+        let timestamp_of = |number: u64| Ok(1000 + number * 10);
+        assert_eq!(bisect_block(1000, 100, timestamp_of).unwrap(), 0);
+        assert_eq!(bisect_block(1050, 100, timestamp_of).unwrap(), 5);
+        assert_eq!(bisect_block(1051, 100, timestamp_of).unwrap(), 6);
+        assert_eq!(bisect_block(0, 100, timestamp_of).unwrap(), 0);
+        assert_eq!(bisect_block(2000, 100, timestamp_of).unwrap(), 100);
+    }
+
+    #[test]
+    fn bisect_block_with_blocks_sharing_a_timestamp() {
+        let timestamps = [10, 20, 20, 20, 30];
+        let timestamp_of = |number: u64| Ok(timestamps[number as usize]);
+        assert_eq!(bisect_block(20, 4, timestamp_of).unwrap(), 1);
+        assert_eq!(bisect_block(21, 4, timestamp_of).unwrap(), 4);
+    }
+
+    #[test]
+    fn fee_history_keeps_the_fee_token() {
+        let tx = AccountTransaction {
+            transaction_hash: String::from("0x1"),
+            block_number: 1,
+            timestamp: 1668384000,
+            actual_fee: U256::exp10(18),
+            fee_unit: FeeUnit::Fri,
+            token_price: Some(2.0),
+        };
+        let csv = fee_history_to_csv(&[tx], None).unwrap();
+        let row = csv.lines().nth(1).unwrap();
+        assert_eq!(
+            row,
+            "0x1,1,1668384000,2022-11-14,1000000000000000000,fri,STRK,1.000000000000000000,2,2,\
+             1.000000000000000000 STRK"
+        );
+    }
+
+    #[test]
+    fn too_long_ranges_are_rejected() {
+        let error =
+            query_account_fee_history("0x1", 0, MAX_HISTORY_BLOCKS, "http://localhost", false)
+                .unwrap_err();
+        assert!(error.to_string().contains("over the maximum"));
+    }
+}
@@ -0,0 +1,1019 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use ethers::types::U256;
+use ethers::utils;
+use eyre::Result;
+use log::debug;
+use tabled::{Alignment, Panel, Style, Table, Tabled};
+
+use crate::abi::SelectorNames;
+use crate::gas_price::median;
+use crate::model::{Block, FeeUnit, TransactionReceipt};
+use crate::resources::{CairoResources, Resource, Weights};
+use crate::trace::{CallPhase, CallTree};
+use crate::{compute_static_tx_fee, query_block, query_tx_trace};
+
+/// Query the blocks of a range, one at a time.
+/// # Arguments
+/// * `from` - The first block of the range.
+/// * `to` - The last block of the range, included.
+/// * `network_gateway_url` - The network gateway URL.
+/// * `visit` - Called with every block, from the oldest to the most recent one.
+pub fn scan_blocks(
+    from: u64,
+    to: u64,
+    network_gateway_url: &str,
+    mut visit: impl FnMut(&Block) -> Result<()>,
+) -> Result<()> {
+    if from > to {
+        return Err(eyre::eyre!("Invalid block range: {} > {}", from, to));
+    }
+    for number in from..=to {
+        debug!("querying block {} for analysis", number);
+        visit(&query_block(&number.to_string(), network_gateway_url)?)?;
+    }
+    Ok(())
+}
+
+/// What the invocations of a contract cost over a range of blocks.
+#[derive(Debug, Clone)]
+pub struct ContractCost {
+    pub contract_address: String,
+    /// The number of transactions which invoked the contract.
+    pub transactions: usize,
+    /// The resources used by the invocations of the contract, including the calls they made.
+    pub resources: CairoResources,
+    /// The gas the invocations are responsible for, through the resource limiting each transaction.
+    pub gas: f32,
+    /// The fees attributed to the invocations, paid in ETH.
+    pub fee_in_wei: U256,
+    /// The fees attributed to the invocations, paid in STRK.
+    pub fee_in_fri: U256,
+}
+
+impl ContractCost {
+    fn new(contract_address: &str) -> Self {
+        Self {
+            contract_address: contract_address.to_string(),
+            transactions: 0,
+            resources: CairoResources::new("contract", 0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+            gas: 0.0,
+            fee_in_wei: U256::zero(),
+            fee_in_fri: U256::zero(),
+        }
+    }
+
+    /// Returns the builtin with the highest weighted usage, if any builtin was used.
+    pub fn dominant_builtin(&self, weights: &Weights) -> Option<Resource> {
+        Resource::ALL
+            .iter()
+            .filter(|&&resource| resource != Resource::Steps)
+            .map(|&resource| {
+                (
+                    resource,
+                    self.resources.get(resource) * weights.get(resource),
+                )
+            })
+            .filter(|(_, weighted)| *weighted > 0.0)
+            .max_by(|(_, x), (_, y)| x.total_cmp(y))
+            .map(|(resource, _)| resource)
+    }
+}
+
+/// Returns the contracts a transaction invoked, along with the resources each invocation used.
+/// The calls of an account are attributed to the contracts it called, while its validation,
+/// fee transfer and own execution are attributed to the account itself.
+/// Legacy transactions, without an account, are attributed to the contract they invoked.
+fn invocations(call_tree: &CallTree) -> Vec<(&str, CairoResources)> {
+    let execute = call_tree.invocation(CallPhase::Execute);
+    let account = match call_tree.invocation(CallPhase::Validate) {
+        Some(validate) => validate,
+        None => {
+            return execute
+                .map(|call| vec![(call.contract_address.as_str(), call.resources)])
+                .unwrap_or_default()
+        }
+    };
+    let mut overhead = account.resources;
+    let mut invocations = vec![];
+    if let Some(execute) = execute {
+        add_resources(&mut overhead, &execute.own_resources());
+        for call in &execute.internal_calls {
+            invocations.push((call.contract_address.as_str(), call.resources));
+        }
+    }
+    if let Some(fee_transfer) = call_tree.invocation(CallPhase::FeeTransfer) {
+        add_resources(&mut overhead, &fee_transfer.resources);
+    }
+    invocations.push((account.contract_address.as_str(), overhead));
+    invocations
+}
+
+fn add_resources(total: &mut CairoResources, resources: &CairoResources) {
+    for resource in Resource::ALL {
+        total.set(resource, total.get(resource) + resources.get(resource));
+    }
+}
+
+/// Rank the contracts invoked over a range of blocks by the gas their invocations are responsible for.
+/// Every transaction of the range is traced, to attribute its resources and fee to the contracts it called.
+/// # Arguments
+/// * `from` - The first block of the range.
+/// * `to` - The last block of the range, included.
+/// * `network_gateway_url` - The network gateway URL.
+/// * `weights` - The weight of each resource.
+/// # Returns
+/// The contracts, from the most to the least expensive, and a caveat for every transaction
+/// left out because its trace has no call tree, such as legacy declarations.
+pub fn query_contract_costs(
+    from: u64,
+    to: u64,
+    network_gateway_url: &str,
+    weights: &Weights,
+) -> Result<(Vec<ContractCost>, Vec<String>)> {
+    let mut costs: HashMap<String, ContractCost> = HashMap::new();
+    let mut caveats = vec![];
+    scan_blocks(from, to, network_gateway_url, |block| {
+        for tx_receipt in block.transaction_receipts()? {
+            let tx_hash = tx_receipt.transaction_hash()?;
+            let trace = query_tx_trace(&tx_hash, network_gateway_url)?;
+            match CallTree::try_from_trace(&trace) {
+                Ok(call_tree) => add_contract_costs(&mut costs, &call_tree, &tx_receipt, weights)?,
+                Err(e) => caveats.push(format!("transaction {tx_hash} is left out: {e}")),
+            }
+        }
+        Ok(())
+    })?;
+    let mut costs: Vec<ContractCost> = costs.into_values().collect();
+    costs.sort_by(|a, b| b.gas.total_cmp(&a.gas));
+    Ok((costs, caveats))
+}
+
+fn add_contract_costs(
+    costs: &mut HashMap<String, ContractCost>,
+    call_tree: &CallTree,
+    tx_receipt: &TransactionReceipt,
+    weights: &Weights,
+) -> Result<()> {
+    let actual_fee = tx_receipt.actual_fee()?;
+    let fee_unit = tx_receipt.fee_unit()?;
+    let (limiting_resource, _) = call_tree.total_resources().limiting_factor(weights);
+    let mut invoked: Vec<&str> = vec![];
+    for (contract_address, resources) in invocations(call_tree) {
+        let cost = costs
+            .entry(contract_address.to_string())
+            .or_insert_with(|| ContractCost::new(contract_address));
+        if !invoked.contains(&contract_address) {
+            invoked.push(contract_address);
+            cost.transactions += 1;
+        }
+        add_resources(&mut cost.resources, &resources);
+        cost.gas += resources.get(limiting_resource) * weights.get(limiting_resource);
+        let fee = call_tree.attribute_fee(&resources, weights, actual_fee);
+        match fee_unit {
+            FeeUnit::Wei => cost.fee_in_wei = cost.fee_in_wei.saturating_add(fee),
+            FeeUnit::Fri => cost.fee_in_fri = cost.fee_in_fri.saturating_add(fee),
+        }
+    }
+    Ok(())
+}
+
+#[derive(Tabled)]
+struct ContractCostRow {
+    contract: String,
+    transactions: usize,
+    gas: f32,
+    #[tabled(rename = "fee (ETH)")]
+    fee_in_eth: String,
+    #[tabled(rename = "fee (STRK)")]
+    fee_in_strk: String,
+    steps: f32,
+    pedersen: f32,
+    range_check: f32,
+    ecdsa: f32,
+    bitwise: f32,
+    ec_op: f32,
+    #[tabled(rename = "dominant builtin")]
+    dominant_builtin: &'static str,
+}
+
+impl ContractCostRow {
+    fn new(cost: &ContractCost, weights: &Weights) -> Result<Self> {
+        Ok(Self {
+            contract: cost.contract_address.clone(),
+            transactions: cost.transactions,
+            gas: cost.gas.round(),
+            fee_in_eth: utils::format_units(cost.fee_in_wei, "ether")?,
+            fee_in_strk: utils::format_units(cost.fee_in_fri, "ether")?,
+            steps: cost.resources.steps,
+            pedersen: cost.resources.pedersen,
+            range_check: cost.resources.range_check,
+            ecdsa: cost.resources.ecdsa,
+            bitwise: cost.resources.bitwise,
+            ec_op: cost.resources.ec_op,
+            dominant_builtin: cost
+                .dominant_builtin(weights)
+                .map(|resource| resource.name())
+                .unwrap_or("-"),
+        })
+    }
+}
+
+/// Renders a contract leaderboard as a table.
+pub fn contract_costs_to_table(costs: &[ContractCost], weights: &Weights) -> Result<String> {
+    let rows = costs
+        .iter()
+        .map(|cost| ContractCostRow::new(cost, weights))
+        .collect::<Result<Vec<_>>>()?;
+    let mut table = Table::new(rows);
+    table.with(Alignment::center()).with(Style::modern());
+    Ok(table.to_string())
+}
+
+/// Formats a contract leaderboard as CSV.
+pub fn contract_costs_to_csv(costs: &[ContractCost], weights: &Weights) -> Result<String> {
+    let mut csv = String::from(
+        "contract_address,transactions,gas,fee_in_wei,fee_in_fri,steps,pedersen,range_check,\
+         ecdsa,bitwise,ec_op,dominant_builtin\n",
+    );
+    for cost in costs {
+        let row = ContractCostRow::new(cost, weights)?;
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            row.contract,
+            row.transactions,
+            row.gas,
+            cost.fee_in_wei,
+            cost.fee_in_fri,
+            row.steps,
+            row.pedersen,
+            row.range_check,
+            row.ecdsa,
+            row.bitwise,
+            row.ec_op,
+            row.dominant_builtin
+        ));
+    }
+    Ok(csv)
+}
+
+/// Formats a contract leaderboard as JSON.
+pub fn contract_costs_to_json(costs: &[ContractCost], weights: &Weights) -> Result<String> {
+    let rows: Vec<serde_json::Value> = costs
+        .iter()
+        .map(|cost| {
+            serde_json::json!({
+                "contract_address": cost.contract_address,
+                "transactions": cost.transactions,
+                "gas": cost.gas.round(),
+                "fee_in_wei": cost.fee_in_wei.to_string(),
+                "fee_in_fri": cost.fee_in_fri.to_string(),
+                "steps": cost.resources.steps,
+                "pedersen": cost.resources.pedersen,
+                "range_check": cost.resources.range_check,
+                "ecdsa": cost.resources.ecdsa,
+                "bitwise": cost.resources.bitwise,
+                "ec_op": cost.resources.ec_op,
+                "dominant_builtin": cost.dominant_builtin(weights).map(|resource| resource.name()),
+            })
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&rows)?)
+}
+
+/// What a single transaction cost, as reported by its receipt.
+#[derive(Debug, Clone)]
+pub struct TransactionCost {
+    pub transaction_hash: String,
+    pub block_number: u64,
+    /// The transaction type, such as `INVOKE_FUNCTION` or `DECLARE`.
+    pub transaction_type: String,
+    /// The contract and entrypoint the transaction invoked, see [`crate::model::Transaction::entry_point`].
+    pub entry_point: Option<(String, String)>,
+    pub actual_fee: U256,
+    pub fee_unit: FeeUnit,
+    /// The actual fee in L1 gas, at the gas price of the block, to compare fees paid in either token.
+    pub gas: U256,
+    pub resources: CairoResources,
+}
+
+impl TransactionCost {
+    /// Returns the amount of a resource used, or the fee in L1 gas if no resource is provided.
+    pub fn amount(&self, resource: Option<Resource>) -> f64 {
+        match resource {
+            Some(resource) => f64::from(self.resources.get(resource)),
+            None => to_f64(self.gas),
+        }
+    }
+}
+
+/// A transaction whose fee stands out among the transactions invoking the same entrypoint.
+#[derive(Debug, Clone)]
+pub struct Outlier {
+    pub transaction: TransactionCost,
+    /// The median fee of the transactions invoking the same entrypoint, in the same token.
+    pub median_fee: U256,
+    /// The ratio of the transaction fee to the median fee.
+    pub ratio: f64,
+}
+
+fn to_f64(amount: U256) -> f64 {
+    amount.to_string().parse().unwrap_or(f64::MAX)
+}
+
+/// Query the cost of every transaction of a range of blocks, from their receipts.
+/// # Arguments
+/// * `from` - The first block of the range.
+/// * `to` - The last block of the range, included.
+/// * `network_gateway_url` - The network gateway URL.
+/// # Returns
+/// The transactions, from the oldest to the most recent one.
+pub fn query_transaction_costs(
+    from: u64,
+    to: u64,
+    network_gateway_url: &str,
+) -> Result<Vec<TransactionCost>> {
+    let mut costs = vec![];
+    scan_blocks(from, to, network_gateway_url, |block| {
+        let block_number = block.block_number()?;
+        let gas_prices = block.gas_prices()?;
+        let transactions = block.transactions()?;
+        let tx_receipts = block.transaction_receipts()?;
+        for (tx, tx_receipt) in transactions.iter().zip(tx_receipts.iter()) {
+            let actual_fee = tx_receipt.actual_fee()?;
+            let fee_unit = tx_receipt.fee_unit()?;
+            let gas_price = gas_prices.l1_gas.in_unit(fee_unit);
+            let gas = if gas_price.is_zero() {
+                U256::zero()
+            } else {
+                compute_static_tx_fee(actual_fee, gas_price)?.gas_units()
+            };
+            costs.push(TransactionCost {
+                transaction_hash: tx_receipt.transaction_hash()?,
+                block_number,
+                transaction_type: tx.transaction_type()?,
+                entry_point: tx.entry_point()?,
+                actual_fee,
+                fee_unit,
+                gas,
+                resources: tx_receipt.resources_used()?,
+            });
+        }
+        Ok(())
+    })?;
+    Ok(costs)
+}
+
+/// Returns the most expensive transactions.
+/// # Arguments
+/// * `costs` - The transactions.
+/// * `top` - The number of transactions to return.
+/// * `resource` - The resource to rank the transactions by, or their fee in L1 gas if not provided.
+pub fn top_transactions(
+    costs: &[TransactionCost],
+    top: usize,
+    resource: Option<Resource>,
+) -> Vec<&TransactionCost> {
+    let mut ranked: Vec<&TransactionCost> = costs
+        .iter()
+        .filter(|cost| cost.amount(resource) > 0.0)
+        .collect();
+    ranked.sort_by(|a, b| b.amount(resource).total_cmp(&a.amount(resource)));
+    ranked.truncate(top);
+    ranked
+}
+
+/// What the transactions compared by [`find_outliers`] have in common.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct GroupKey<'a> {
+    /// The contract address and selector invoked, normalized.
+    entry_point: Option<(String, U256)>,
+    transaction_type: &'a str,
+    fee_unit: FeeUnit,
+}
+
+/// Find the transactions whose fee is much higher than usual for what they invoked.
+/// Transactions are grouped by contract and entrypoint, or by type when they invoke none,
+/// and by fee token, then compared with the median fee of their group.
+/// # Arguments
+/// * `costs` - The transactions.
+/// * `factor` - How many times the median fee a transaction must pay to be an outlier.
+/// * `min_samples` - The number of transactions a group needs for its median to be meaningful.
+/// # Returns
+/// The outliers, from the highest to the lowest ratio.
+/// # Errors
+/// If the factor isn't a positive number.
+pub fn find_outliers(
+    costs: &[TransactionCost],
+    factor: f64,
+    min_samples: usize,
+) -> Result<Vec<Outlier>> {
+    if !(factor.is_finite() && factor > 0.0) {
+        return Err(eyre::eyre!(
+            "Invalid outlier factor {factor}, expected a positive number"
+        ));
+    }
+    let mut groups: HashMap<GroupKey, Vec<&TransactionCost>> = HashMap::new();
+    for cost in costs {
+        // Selectors are normalized, since their padding differs between transactions.
+        let entry_point = cost.entry_point.as_ref().and_then(|(contract, selector)| {
+            Some((
+                U256::from_str(contract).ok()?.to_string(),
+                U256::from_str(selector).ok()?,
+            ))
+        });
+        groups
+            .entry(GroupKey {
+                entry_point,
+                transaction_type: &cost.transaction_type,
+                fee_unit: cost.fee_unit,
+            })
+            .or_default()
+            .push(cost);
+    }
+
+    let mut outliers = vec![];
+    for group in groups.values().filter(|group| group.len() >= min_samples) {
+        let mut fees: Vec<U256> = group.iter().map(|cost| cost.actual_fee).collect();
+        fees.sort();
+        let median_fee = median(&fees);
+        if median_fee.is_zero() {
+            continue;
+        }
+        for cost in group {
+            let ratio = to_f64(cost.actual_fee) / to_f64(median_fee);
+            if ratio >= factor {
+                outliers.push(Outlier {
+                    transaction: (*cost).clone(),
+                    median_fee,
+                    ratio,
+                });
+            }
+        }
+    }
+    outliers.sort_by(|a, b| b.ratio.total_cmp(&a.ratio));
+    Ok(outliers)
+}
+
+fn format_fee(fee: U256, fee_unit: FeeUnit) -> Result<String> {
+    Ok(format!(
+        "{} {}",
+        utils::format_units(fee, "ether")?,
+        fee_unit.token_symbol()
+    ))
+}
+
+fn format_entry_point(cost: &TransactionCost, names: &SelectorNames) -> (String, String) {
+    match &cost.entry_point {
+        Some((contract, selector)) => (
+            contract.clone(),
+            names.name(selector).unwrap_or(selector).to_string(),
+        ),
+        None => (String::from("-"), cost.transaction_type.to_lowercase()),
+    }
+}
+
+#[derive(Tabled)]
+struct TransactionCostRow {
+    rank: usize,
+    #[tabled(rename = "transaction hash")]
+    transaction_hash: String,
+    block: u64,
+    contract: String,
+    entrypoint: String,
+    fee: String,
+    amount: f64,
+}
+
+#[derive(Tabled)]
+struct OutlierRow {
+    #[tabled(rename = "transaction hash")]
+    transaction_hash: String,
+    block: u64,
+    contract: String,
+    entrypoint: String,
+    fee: String,
+    #[tabled(rename = "median fee")]
+    median_fee: String,
+    ratio: String,
+}
+
+/// Renders the most expensive transactions, by fee and by each resource, followed by the outliers.
+/// # Arguments
+/// * `costs` - The transactions.
+/// * `top` - The number of transactions to show per ranking.
+/// * `outliers` - The outliers, see [`find_outliers`].
+/// * `factor` - The factor the outliers were found with.
+/// * `names` - The names of the entrypoints.
+pub fn transaction_costs_to_table(
+    costs: &[TransactionCost],
+    top: usize,
+    outliers: &[Outlier],
+    factor: f64,
+    names: &SelectorNames,
+) -> Result<String> {
+    let mut tables = vec![];
+    let rankings = std::iter::once(None).chain(Resource::ALL.iter().map(|&r| Some(r)));
+    for resource in rankings {
+        let ranked = top_transactions(costs, top, resource);
+        if ranked.is_empty() {
+            continue;
+        }
+        let rows = ranked
+            .iter()
+            .enumerate()
+            .map(|(index, cost)| {
+                let (contract, entrypoint) = format_entry_point(cost, names);
+                Ok(TransactionCostRow {
+                    rank: index + 1,
+                    transaction_hash: cost.transaction_hash.clone(),
+                    block: cost.block_number,
+                    contract,
+                    entrypoint,
+                    fee: format_fee(cost.actual_fee, cost.fee_unit)?,
+                    amount: cost.amount(resource),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let title = format!(
+            "Top {} transactions by {}",
+            rows.len(),
+            resource.map(|r| r.name()).unwrap_or("fee (L1 gas)")
+        );
+        let mut table = Table::new(rows);
+        table
+            .with(Panel::header(title))
+            .with(Alignment::center())
+            .with(Style::modern());
+        tables.push(table.to_string());
+    }
+
+    let title = format!(
+        "{} outliers, paying at least {factor}x the median fee of their entrypoint",
+        outliers.len()
+    );
+    let rows = outliers
+        .iter()
+        .map(|outlier| {
+            let cost = &outlier.transaction;
+            let (contract, entrypoint) = format_entry_point(cost, names);
+            Ok(OutlierRow {
+                transaction_hash: cost.transaction_hash.clone(),
+                block: cost.block_number,
+                contract,
+                entrypoint,
+                fee: format_fee(cost.actual_fee, cost.fee_unit)?,
+                median_fee: format_fee(outlier.median_fee, cost.fee_unit)?,
+                ratio: format!("{:.1}x", outlier.ratio),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut table = Table::new(rows);
+    table
+        .with(Panel::header(title))
+        .with(Alignment::center())
+        .with(Style::modern());
+    tables.push(table.to_string());
+    Ok(tables.join("\n\n"))
+}
+
+/// An approximation of the number of Cairo steps a block can hold.
+/// The sequencer doesn't publish its block limits, so this is an order of magnitude rather than a
+/// documented value, like the builtin capacities derived from it.
+pub const DEFAULT_BLOCK_MAX_STEPS: f32 = 40_000_000.0;
+
+/// Returns the resources a block can hold.
+/// A weight is the share of a proof one unit of a resource occupies, so the capacity of each builtin
+/// follows from the steps capacity: a builtin weighing 32 times a step fits 32 times less often.
+/// # Arguments
+/// * `max_steps` - The number of Cairo steps a block can hold.
+/// * `weights` - The weight of each resource.
+pub fn block_capacity(max_steps: f32, weights: &Weights) -> CairoResources {
+    let mut capacity = CairoResources::new("capacity", 0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+    for resource in Resource::ALL {
+        let weight = weights.get(resource);
+        if weight > 0.0 {
+            capacity.set(resource, max_steps * weights.steps / weight);
+        }
+    }
+    capacity
+}
+
+/// Says how [`block_capacity`] is derived, to go along with the utilisation it yields.
+pub const BLOCK_CAPACITY_NOTE: &str =
+    "Builtin capacities are approximated from the block steps capacity and the resource weights.";
+
+/// The number of units of each resource used.
+/// Counts are integers, so that adding up many blocks doesn't lose precision.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceCounts([u64; Resource::ALL.len()]);
+
+impl ResourceCounts {
+    /// Returns the number of units of a resource used.
+    pub fn get(&self, resource: Resource) -> u64 {
+        self.0[resource as usize]
+    }
+
+    /// Adds the resources used by a transaction, which are whole numbers.
+    fn add_resources(&mut self, resources: &CairoResources) {
+        for resource in Resource::ALL {
+            self.0[resource as usize] += resources.get(resource) as u64;
+        }
+    }
+
+    fn add(&mut self, other: &ResourceCounts) {
+        for resource in Resource::ALL {
+            self.0[resource as usize] += other.get(resource);
+        }
+    }
+}
+
+/// The fees paid in one token, and by how many transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeTotal {
+    pub transactions: usize,
+    pub total: U256,
+}
+
+impl FeeTotal {
+    fn add(&mut self, other: &FeeTotal) {
+        self.transactions += other.transactions;
+        self.total = self.total.saturating_add(other.total);
+    }
+
+    /// Returns the average fee per transaction, if any transaction paid in this token.
+    pub fn average(&self) -> Option<U256> {
+        match self.transactions {
+            0 => None,
+            transactions => Some(self.total / transactions),
+        }
+    }
+}
+
+/// What the transactions of a block, or of a range of blocks, used and paid.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockUsage {
+    pub first_block: u64,
+    pub last_block: u64,
+    pub transactions: usize,
+    pub resources: ResourceCounts,
+    pub fees_in_wei: FeeTotal,
+    pub fees_in_fri: FeeTotal,
+}
+
+impl BlockUsage {
+    /// Adds up what the transactions of a block used and paid.
+    pub fn try_from_block(block: &Block) -> Result<Self> {
+        let block_number = block.block_number()?;
+        let mut usage = Self::empty(block_number);
+        for tx_receipt in block.transaction_receipts()? {
+            usage.transactions += 1;
+            usage.resources.add_resources(&tx_receipt.resources_used()?);
+            let fee = FeeTotal {
+                transactions: 1,
+                total: tx_receipt.actual_fee()?,
+            };
+            match tx_receipt.fee_unit()? {
+                FeeUnit::Wei => usage.fees_in_wei.add(&fee),
+                FeeUnit::Fri => usage.fees_in_fri.add(&fee),
+            }
+        }
+        Ok(usage)
+    }
+
+    fn empty(block_number: u64) -> Self {
+        Self {
+            first_block: block_number,
+            last_block: block_number,
+            transactions: 0,
+            resources: ResourceCounts::default(),
+            fees_in_wei: FeeTotal::default(),
+            fees_in_fri: FeeTotal::default(),
+        }
+    }
+
+    /// Returns the number of blocks covered.
+    pub fn blocks(&self) -> u64 {
+        self.last_block - self.first_block + 1
+    }
+
+    /// Returns the share of the capacity of the covered blocks a resource used.
+    pub fn utilisation(&self, resource: Resource, capacity: &CairoResources) -> f32 {
+        let capacity = f64::from(capacity.get(resource)) * self.blocks() as f64;
+        if capacity > 0.0 {
+            (self.resources.get(resource) as f64 / capacity) as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the resource closest to the capacity, which limits how many more transactions fit,
+    /// along with its utilisation.
+    pub fn limiting_utilisation(&self, capacity: &CairoResources) -> (Resource, f32) {
+        Resource::ALL
+            .iter()
+            .map(|&resource| (resource, self.utilisation(resource, capacity)))
+            .max_by(|(_, x), (_, y)| x.total_cmp(y))
+            .unwrap_or((Resource::Steps, 0.0))
+    }
+}
+
+/// Report what the blocks of a range used, against their capacity, and paid.
+/// # Arguments
+/// * `from` - The first block of the range.
+/// * `to` - The last block of the range, included.
+/// * `network_gateway_url` - The network gateway URL.
+/// # Returns
+/// The usage of every block, from the oldest to the most recent one.
+pub fn query_block_usages(
+    from: u64,
+    to: u64,
+    network_gateway_url: &str,
+) -> Result<Vec<BlockUsage>> {
+    let mut usages = vec![];
+    scan_blocks(from, to, network_gateway_url, |block| {
+        usages.push(BlockUsage::try_from_block(block)?);
+        Ok(())
+    })?;
+    Ok(usages)
+}
+
+/// Adds up the usage of consecutive blocks.
+/// # Returns
+/// `None` if no block is provided.
+pub fn range_usage(usages: &[BlockUsage]) -> Option<BlockUsage> {
+    let (first, rest) = usages.split_first()?;
+    let mut range = *first;
+    for usage in rest {
+        range.first_block = range.first_block.min(usage.first_block);
+        range.last_block = range.last_block.max(usage.last_block);
+        range.transactions += usage.transactions;
+        range.resources.add(&usage.resources);
+        range.fees_in_wei.add(&usage.fees_in_wei);
+        range.fees_in_fri.add(&usage.fees_in_fri);
+    }
+    Some(range)
+}
+
+fn format_average_fee(fees: &FeeTotal) -> Result<String> {
+    match fees.average() {
+        Some(average) => Ok(utils::format_units(average, "ether")?),
+        None => Ok(String::from("-")),
+    }
+}
+
+#[derive(Tabled)]
+struct BlockUsageRow {
+    block: String,
+    transactions: usize,
+    steps: String,
+    pedersen: String,
+    range_check: String,
+    ecdsa: String,
+    bitwise: String,
+    ec_op: String,
+    utilisation: String,
+    #[tabled(rename = "fees (ETH)")]
+    fees_in_eth: String,
+    #[tabled(rename = "average fee (ETH)")]
+    average_fee_in_eth: String,
+    #[tabled(rename = "fees (STRK)")]
+    fees_in_strk: String,
+    #[tabled(rename = "average fee (STRK)")]
+    average_fee_in_strk: String,
+}
+
+impl BlockUsageRow {
+    fn new(usage: &BlockUsage, capacity: &CairoResources) -> Result<Self> {
+        let resource = |resource: Resource| {
+            format!(
+                "{} ({:.1}%)",
+                usage.resources.get(resource),
+                usage.utilisation(resource, capacity) * 100.0
+            )
+        };
+        let (limiting_resource, utilisation) = usage.limiting_utilisation(capacity);
+        Ok(Self {
+            block: match usage.blocks() {
+                1 => usage.first_block.to_string(),
+                _ => format!("{}-{}", usage.first_block, usage.last_block),
+            },
+            transactions: usage.transactions,
+            steps: resource(Resource::Steps),
+            pedersen: resource(Resource::Pedersen),
+            range_check: resource(Resource::RangeCheck),
+            ecdsa: resource(Resource::Ecdsa),
+            bitwise: resource(Resource::Bitwise),
+            ec_op: resource(Resource::EcOp),
+            utilisation: format!("{:.1}% ({})", utilisation * 100.0, limiting_resource.name()),
+            fees_in_eth: utils::format_units(usage.fees_in_wei.total, "ether")?,
+            average_fee_in_eth: format_average_fee(&usage.fees_in_wei)?,
+            fees_in_strk: utils::format_units(usage.fees_in_fri.total, "ether")?,
+            average_fee_in_strk: format_average_fee(&usage.fees_in_fri)?,
+        })
+    }
+}
+
+/// Renders the usage of every block as a table, followed by the usage of the whole range.
+/// Each resource is shown with the share of the block capacity it used.
+pub fn block_usages_to_table(usages: &[BlockUsage], capacity: &CairoResources) -> Result<String> {
+    let mut rows = usages
+        .iter()
+        .map(|usage| BlockUsageRow::new(usage, capacity))
+        .collect::<Result<Vec<_>>>()?;
+    let mut table = match range_usage(usages) {
+        Some(range) if usages.len() > 1 => {
+            let peak = usages
+                .iter()
+                .map(|usage| usage.limiting_utilisation(capacity).1)
+                .fold(0.0, f32::max);
+            rows.push(BlockUsageRow::new(&range, capacity)?);
+            let mut table = Table::new(rows);
+            table.with(Panel::footer(format!(
+                "The last row adds up the range. Peak block utilisation: {:.1}%\n{BLOCK_CAPACITY_NOTE}",
+                peak * 100.0
+            )));
+            table
+        }
+        _ => {
+            let mut table = Table::new(rows);
+            table.with(Panel::footer(BLOCK_CAPACITY_NOTE));
+            table
+        }
+    };
+    table.with(Alignment::center()).with(Style::modern());
+    Ok(table.to_string())
+}
+
+/// Formats the usage of every block as CSV.
+/// The utilisation is an approximation, see [`BLOCK_CAPACITY_NOTE`].
+pub fn block_usages_to_csv(usages: &[BlockUsage], capacity: &CairoResources) -> Result<String> {
+    let mut csv = String::from(
+        "block_number,transactions,steps,pedersen,range_check,ecdsa,bitwise,ec_op,utilisation,\
+         limiting_resource,fees_in_wei,eth_transactions,fees_in_fri,strk_transactions\n",
+    );
+    for usage in usages {
+        let (limiting_resource, utilisation) = usage.limiting_utilisation(capacity);
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            usage.first_block,
+            usage.transactions,
+            usage.resources.get(Resource::Steps),
+            usage.resources.get(Resource::Pedersen),
+            usage.resources.get(Resource::RangeCheck),
+            usage.resources.get(Resource::Ecdsa),
+            usage.resources.get(Resource::Bitwise),
+            usage.resources.get(Resource::EcOp),
+            utilisation,
+            limiting_resource.name(),
+            usage.fees_in_wei.total,
+            usage.fees_in_wei.transactions,
+            usage.fees_in_fri.total,
+            usage.fees_in_fri.transactions,
+        ));
+    }
+    Ok(csv)
+}
+
+fn block_usage_to_json(usage: &BlockUsage, capacity: &CairoResources) -> serde_json::Value {
+    let (limiting_resource, utilisation) = usage.limiting_utilisation(capacity);
+    let resources: serde_json::Map<String, serde_json::Value> = Resource::ALL
+        .iter()
+        .map(|&resource| {
+            (
+                resource.name().to_string(),
+                serde_json::json!({
+                    "used": usage.resources.get(resource),
+                    "utilisation": usage.utilisation(resource, capacity),
+                }),
+            )
+        })
+        .collect();
+    let fees = |fees: &FeeTotal| {
+        serde_json::json!({
+            "transactions": fees.transactions,
+            "total": fees.total.to_string(),
+            "average": fees.average().map(|average| average.to_string()),
+        })
+    };
+    serde_json::json!({
+        "first_block": usage.first_block,
+        "last_block": usage.last_block,
+        "transactions": usage.transactions,
+        "resources": resources,
+        "utilisation": utilisation,
+        "limiting_resource": limiting_resource.name(),
+        "fees_in_wei": fees(&usage.fees_in_wei),
+        "fees_in_fri": fees(&usage.fees_in_fri),
+    })
+}
+
+/// Formats the usage of every block, and of the whole range, as JSON.
+pub fn block_usages_to_json(usages: &[BlockUsage], capacity: &CairoResources) -> Result<String> {
+    let blocks: Vec<serde_json::Value> = usages
+        .iter()
+        .map(|usage| block_usage_to_json(usage, capacity))
+        .collect();
+    let block_capacity: serde_json::Map<String, serde_json::Value> = Resource::ALL
+        .iter()
+        .map(|&resource| (resource.name().to_string(), capacity.get(resource).into()))
+        .collect();
+    let report = serde_json::json!({
+        "capacity": block_capacity,
+        "capacity_note": BLOCK_CAPACITY_NOTE,
+        "blocks": blocks,
+        "range": range_usage(usages).map(|range| block_usage_to_json(&range, capacity)),
+    });
+    Ok(serde_json::to_string_pretty(&report)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(hash: &str, selector: &str, actual_fee: u64) -> TransactionCost {
+        TransactionCost {
+            transaction_hash: hash.to_string(),
+            block_number: 1,
+            transaction_type: String::from("INVOKE_FUNCTION"),
+            entry_point: Some((String::from("0x1"), selector.to_string())),
+            actual_fee: actual_fee.into(),
+            fee_unit: FeeUnit::Wei,
+            gas: U256::zero(),
+            resources: CairoResources::new("used", 0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+        }
+    }
+
+    fn outlier_hashes(outliers: &[Outlier]) -> Vec<&str> {
+        outliers
+            .iter()
+            .map(|outlier| outlier.transaction.transaction_hash.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn outliers_are_compared_within_their_group() {
+        let costs = vec![
+            transaction("0xa1", "0x10", 100),
+            transaction("0xa2", "0x10", 110),
+            transaction("0xa3", "0x10", 90),
+            transaction("0xa4", "0x10", 1000),
+            // Another entrypoint, where paying 1000 is usual.
+            transaction("0xb1", "0x20", 1000),
+            transaction("0xb2", "0x20", 1000),
+            transaction("0xb3", "0x20", 1000),
+        ];
+        let outliers = find_outliers(&costs, 5.0, 3).unwrap();
+        assert_eq!(outlier_hashes(&outliers), vec!["0xa4"]);
+        // The median of an even group is the average of its two middle fees.
+        assert_eq!(outliers[0].median_fee, U256::from(105));
+    }
+
+    #[test]
+    fn outliers_of_small_groups() {
+        let costs = vec![
+            transaction("0xa1", "0x10", 100),
+            transaction("0xa2", "0x10", 300),
+            transaction("0xb1", "0x20", 1000),
+        ];
+        // A group of two has the average of its fees as median.
+        let outliers = find_outliers(&costs, 1.5, 1).unwrap();
+        assert_eq!(outlier_hashes(&outliers), vec!["0xa2"]);
+        assert_eq!(outliers[0].median_fee, U256::from(200));
+        // A single transaction is its own median, and never an outlier.
+        assert!(find_outliers(&costs[2..], 1.0 + f64::EPSILON, 1)
+            .unwrap()
+            .is_empty());
+        // Groups smaller than the minimum are skipped.
+        assert!(find_outliers(&costs, 1.5, 3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn block_usage_splits_fees_by_unit() {
+        let receipts: Vec<json::JsonValue> = ["tests/example.json", "tests/example_v3.json"]
+            .iter()
+            .map(|filename| json::parse(&std::fs::read_to_string(filename).unwrap()).unwrap())
+            .collect();
+        let block = json::object! {
+            block_number: 634000,
+            transaction_receipts: receipts,
+        };
+        let usage = BlockUsage::try_from_block(&Block(block.dump())).unwrap();
+        assert_eq!(usage.transactions, 2);
+        assert_eq!(usage.fees_in_wei.transactions, 1);
+        assert_eq!(usage.fees_in_fri.transactions, 1);
+        assert_eq!(
+            usage.fees_in_fri.total,
+            U256::from_str("0x2386f26fc10000").unwrap()
+        );
+    }
+
+    #[test]
+    fn outlier_factor_must_be_positive() {
+        let costs = vec![transaction("0xa1", "0x10", 100)];
+        assert!(find_outliers(&costs, 0.0, 1).is_err());
+        assert!(find_outliers(&costs, -1.0, 1).is_err());
+        assert!(find_outliers(&costs, f64::NAN, 1).is_err());
+    }
+}
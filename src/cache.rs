@@ -0,0 +1,159 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::{debug, warn};
+
+use crate::network::Network;
+use crate::setting::Setting;
+
+/// How long responses about the latest or pending state are cached.
+pub const TTL: Duration = Duration::from_secs(10);
+
+/// How [`crate::http_get`] uses the on-disk response cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Responses are neither read from nor written to the cache.
+    Disabled,
+    /// Responses are read from the cache when fresh, and written to it.
+    Enabled,
+    /// Responses are always fetched, and written to the cache.
+    Refresh,
+}
+
+static CACHE_MODE: Setting<CacheMode> = Setting::new(CacheMode::Disabled);
+
+/// Sets how responses are cached. The cache is disabled by default.
+pub fn set_cache_mode(mode: CacheMode) {
+    CACHE_MODE.set(mode);
+}
+
+/// Returns the cache directory, `$XDG_CACHE_HOME/howmuch` or `~/.cache/howmuch`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    Some(base.join("howmuch"))
+}
+
+/// Returns the cached response to a GET request, if there is a fresh one.
+pub fn get(url: &str) -> Option<String> {
+    if CACHE_MODE.get() != CacheMode::Enabled {
+        return None;
+    }
+    let path = cache_path(url)?;
+    let body = std::fs::read_to_string(&path).ok()?;
+    if !is_final(url, &body) {
+        let age = std::fs::metadata(&path)
+            .ok()?
+            .modified()
+            .ok()?
+            .elapsed()
+            .ok()?;
+        if age > TTL {
+            return None;
+        }
+    }
+    debug!("cache hit for {}", url);
+    Some(body)
+}
+
+/// Caches the response to a GET request.
+/// Failing to write the cache is not an error, the response is just not cached.
+pub fn put(url: &str, body: &str) {
+    if CACHE_MODE.get() == CacheMode::Disabled || is_error(body) {
+        return;
+    }
+    let path = match cache_path(url) {
+        Some(path) => path,
+        None => return,
+    };
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, body));
+    if let Err(e) = written {
+        warn!("failed to cache {}: {}", url, e);
+    }
+}
+
+/// Maps a feeder gateway URL to `<chain id>/<endpoint>/<parameters>.json`.
+/// URLs which are not feeder gateway queries, such as price oracles, are not cached.
+fn cache_path(url: &str) -> Option<PathBuf> {
+    let (base, query) = url.split_once('?')?;
+    let (network_gateway_url, endpoint) = base.rsplit_once('/')?;
+    if !endpoint.starts_with("get_") {
+        return None;
+    }
+    let network = match Network::from_gateway_url(network_gateway_url) {
+        Some(network) => network.chain_id().to_string(),
+        None => sanitize(
+            network_gateway_url
+                .trim_start_matches("https://")
+                .trim_start_matches("http://"),
+        ),
+    };
+    let parameters: Vec<&str> = query
+        .split('&')
+        .filter_map(|parameter| parameter.split_once('=').map(|(_, value)| value))
+        .collect();
+    Some(
+        cache_dir()?
+            .join(network)
+            .join(sanitize(endpoint))
+            .join(format!("{}.json", sanitize(&parameters.join("_")))),
+    )
+}
+
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Returns whether a response can't change anymore.
+/// Anything about the latest or pending state, or not yet accepted, may still change.
+/// So does the class of a contract, which can be upgraded, unless it is queried at a given block.
+/// Only the public networks are trusted to never change their history:
+/// a devnet or a mock gateway can be reset, and serve different blocks under the same numbers.
+fn is_final(url: &str, body: &str) -> bool {
+    let is_public = url
+        .split_once('?')
+        .and_then(|(base, _)| base.rsplit_once('/'))
+        .and_then(|(network_gateway_url, _)| Network::from_gateway_url(network_gateway_url))
+        .is_some();
+    if !is_public {
+        return false;
+    }
+    if url.contains("=latest") || url.contains("=pending") {
+        return false;
+    }
+    if url.contains("/get_class_hash_at?") && !url.contains("blockNumber=") {
+        return false;
+    }
+    let j = match json::parse(body) {
+        Ok(j) => j,
+        Err(_) => return false,
+    };
+    let status = j["finality_status"]
+        .as_str()
+        .or_else(|| j["status"].as_str());
+    !matches!(
+        status,
+        Some("NOT_RECEIVED") | Some("RECEIVED") | Some("PENDING")
+    )
+}
+
+/// Returns whether a response is a gateway error, which must not be cached.
+fn is_error(body: &str) -> bool {
+    match json::parse(body) {
+        Ok(j) => j.has_key("code") && j.has_key("message"),
+        Err(_) => true,
+    }
+}
@@ -1,4 +1,17 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use ethers::types::U256;
+
+use crate::account::AccountLabel;
+use crate::accounting::ReportFormat;
+use crate::analyze::DEFAULT_BLOCK_MAX_STEPS;
+use crate::currencies::DEFAULT_PRICE_ORACLE_URL;
+use crate::gas_price::{BlockWindow, HistoryFormat, PriceStat};
+use crate::model::{parse_u256_str, FeeUnit, GasDimension, GasPrices, ResourcePrice};
+use crate::network::Network;
+use crate::resources::Weights;
+use crate::scenario::{ResourceScale, Scenario};
+use crate::trace::{FlameWeight, TraceFormat};
+use crate::units::Unit;
 
 pub const DEFAULT_SOURCE_NETWORK_GATEWAY_URL: &str = "https://alpha4-2.starknet.io/feeder_gateway";
 pub const DEFAULT_DESTINATION_NETWORK_GATEWAY_URL: &str =
@@ -19,6 +32,21 @@ pub struct Cli {
     /// List of supported commands.
     #[command(subcommand)]
     pub command: Commands,
+    /// Neither read nor write the response cache.
+    #[arg(long, global = true, conflicts_with = "refresh")]
+    pub no_cache: bool,
+    /// Ignore cached responses, and cache the fresh ones.
+    #[arg(long, global = true)]
+    pub refresh: bool,
+    /// Store every response fetched in the directory.
+    #[arg(long, global = true, value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<String>,
+    /// Serve the responses stored by `--record` from the directory, instead of the network.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub replay: Option<String>,
+    /// The price oracle URL, serving the coingecko API.
+    #[arg(long, global = true, value_name = "URL", default_value = DEFAULT_PRICE_ORACLE_URL)]
+    pub price_oracle_url: String,
 }
 
 /// List of supported commands.
@@ -26,7 +54,26 @@ pub struct Cli {
 pub enum Commands {
     /// Ethereum related subcommands
     #[command(about = "Fees related subcommands")]
-    Fees(FeesCommands),
+    Fees(Box<FeesCommands>),
+    /// Gas price related subcommands
+    #[command(about = "Gas price related subcommands")]
+    GasPrice(GasPriceCommands),
+    /// Account related subcommands.
+    Account(AccountCommands),
+    /// Analysis of the transactions of a range of blocks.
+    Analyze(Box<AnalyzeCommands>),
+    /// Convert an amount to other units.
+    Convert {
+        /// The amount, either a `0x` prefixed integer or a decimal number.
+        #[arg(value_name = "AMOUNT")]
+        amount: String,
+        /// The unit the amount is denominated in.
+        #[arg(long, value_enum, default_value = "wei")]
+        from: Unit,
+        /// The units to convert the amount to, USD uses the price oracle.
+        #[arg(long, value_enum, required = true)]
+        to: Vec<Unit>,
+    },
 }
 
 /// Fees related commands.
@@ -43,8 +90,13 @@ pub enum FeesSubCommands {
     /// Estimate fee from a network to another.
     EstimateOnNetwork {
         /// The transaction hash on the source network.
-        #[arg(short, long, value_name = "TX_HASH")]
-        tx_hash: String,
+        #[arg(
+            short,
+            long,
+            value_name = "TX_HASH",
+            required_unless_present = "source_receipt_file"
+        )]
+        tx_hash: Option<String>,
         /// The source network gateway URL.
         /// If not provided, the default is goerli 2 testnet.
         #[arg(long, value_name = "SOURCE_NETWORK_GATEWAY_URL", default_value = DEFAULT_SOURCE_NETWORK_GATEWAY_URL)]
@@ -55,7 +107,7 @@ pub enum FeesSubCommands {
         #[arg(long, value_name = "DESTINATION_NETWORK_GATEWAY_URL", default_value = DEFAULT_DESTINATION_NETWORK_GATEWAY_URL)]
         destination_network_gateway_url: String,
         /// The source block number.
-        /// If not provided, the default is the latest block.
+        /// If not provided, the default is the block which included the transaction.
         #[arg(long, value_name = "SOURCE_BLOCK_NUMBER")]
         source_block_number: Option<u32>,
         /// The destination block number.
@@ -65,6 +117,153 @@ pub enum FeesSubCommands {
         /// Allows the output to display the dollar cost estimation
         #[arg(long, value_name = "BOOL", default_value = "false")]
         usd: Option<bool>,
+        /// The gas price statistic used to price the destination fee.
+        /// If not provided but a window is, the default is the median.
+        #[arg(long, value_enum, requires = "window")]
+        price_stat: Option<PriceStat>,
+        /// The window of destination blocks, ending at the destination block, the statistic is computed over.
+        /// Either a number of blocks (e.g. `100`) or a duration (e.g. `90s`, `30m`, `2h`, `1d`),
+        /// both capped at 1000 blocks.
+        #[arg(long, value_name = "WINDOW")]
+        window: Option<BlockWindow>,
+        /// File containing the source transaction receipt.
+        /// If provided, overrides `tx_hash` and `source_network_gateway_url`.
+        #[arg(long, value_name = "FILE")]
+        source_receipt_file: Option<String>,
+        /// File containing the source block.
+        /// If provided, overrides `source_block_number`.
+        #[arg(long, value_name = "FILE")]
+        source_block_file: Option<String>,
+        /// File containing the destination block.
+        /// If provided, overrides `destination_block_number` and `destination_network_gateway_url`.
+        #[arg(long, value_name = "FILE", conflicts_with = "window")]
+        destination_block_file: Option<String>,
+        /// The destination gas price, in the unit the fee is paid in.
+        /// Either a single L1 gas price or `l1_gas=<PRICE>,l1_data_gas=<PRICE>,l2_gas=<PRICE>`.
+        /// If provided, overrides the destination block.
+        #[arg(
+            long,
+            value_name = "PRICE",
+            value_parser = parse_gas_prices,
+            conflicts_with_all = ["window", "destination_block_file"]
+        )]
+        destination_gas_price: Option<GasPrices>,
+        /// Print the full fee derivation, with every intermediate value and its unit.
+        #[arg(long)]
+        explain: bool,
+        /// The unit fees are displayed in.
+        /// If not provided, the default is the token paying the fee.
+        #[arg(long, value_enum)]
+        unit: Option<Unit>,
+        /// The maximum number of Cairo steps a transaction can run on the destination network.
+        /// If not provided, the default is the limit of the destination network, or of mainnet.
+        #[arg(long, value_name = "STEPS")]
+        max_steps: Option<u64>,
+        /// The maximum number of calldata felts of a transaction on the destination network.
+        /// If not provided, the default is the limit of the destination network, or of mainnet.
+        #[arg(long, value_name = "FELTS")]
+        max_calldata_length: Option<usize>,
+        /// The maximum size of a declared class on the destination network, in bytes.
+        /// If not provided, the default is the limit of the destination network, or of mainnet.
+        #[arg(long, value_name = "BYTES")]
+        max_class_size: Option<usize>,
+        /// Check the transaction against the execution limits of the destination network,
+        /// warning about each one it exceeds. Implied by the limits flags.
+        #[arg(long)]
+        check_limits: bool,
+        /// Fail when the transaction exceeds the execution limits of the destination network,
+        /// instead of warning about it, or when those limits are unknown.
+        #[arg(long)]
+        strict_limits: bool,
+    },
+    /// Output the call tree of a transaction, with the resources used and the fee attributed to each call.
+    Trace {
+        /// The transaction hash.
+        #[arg(
+            short,
+            long,
+            value_name = "TX_HASH",
+            required_unless_present = "trace_file"
+        )]
+        tx_hash: Option<String>,
+        /// The network gateway URL.
+        #[arg(long, value_name = "GATEWAY_URL", default_value = DEFAULT_SOURCE_NETWORK_GATEWAY_URL)]
+        gateway_url: String,
+        /// File containing the transaction trace.
+        /// If provided, overrides `tx_hash` and `gateway_url`.
+        #[arg(long, value_name = "FILE")]
+        trace_file: Option<String>,
+        /// File containing the transaction receipt, whose actual fee is attributed to the calls.
+        /// If not provided, the receipt is queried when `tx_hash` is.
+        #[arg(long, value_name = "FILE")]
+        transaction_file: Option<String>,
+        /// The unit fees are displayed in.
+        /// If not provided, the default is the token paying the fee.
+        #[arg(long, value_enum)]
+        unit: Option<Unit>,
+        /// The output format.
+        #[arg(long, value_enum, default_value = "tree")]
+        format: TraceFormat,
+        /// Files containing contract classes or ABIs, to name the called entrypoints.
+        #[arg(long = "abi-file", value_name = "FILE")]
+        abi_files: Vec<String>,
+        /// Query the class of every called contract, to name the called entrypoints.
+        #[arg(long)]
+        fetch_abis: bool,
+        /// What the width of a frame represents, with the folded format.
+        #[arg(long, value_enum, default_value = "steps")]
+        flame_weight: FlameWeight,
+        /// The file to write the output to.
+        /// If not provided, the output is printed.
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+        #[command(flatten)]
+        weights: WeightsArgs,
+    },
+    /// Compare the validation and fee transfer overhead of account implementations.
+    Overhead {
+        /// The hashes of the transactions to analyze.
+        #[arg(
+            short,
+            long = "tx-hash",
+            value_name = "TX_HASH",
+            required_unless_present = "trace_files"
+        )]
+        tx_hashes: Vec<String>,
+        /// The network gateway URL.
+        #[arg(long, value_name = "GATEWAY_URL", default_value = DEFAULT_SOURCE_NETWORK_GATEWAY_URL)]
+        gateway_url: String,
+        /// Files containing transaction traces to analyze.
+        #[arg(long = "trace-file", value_name = "FILE")]
+        trace_files: Vec<String>,
+        /// A name for the accounts of a class, e.g. `argent=0x1a73...`.
+        /// Accounts without a name are shown by class hash.
+        #[arg(long = "account-label", value_name = "NAME=CLASS_HASH")]
+        account_labels: Vec<AccountLabel>,
+        #[command(flatten)]
+        weights: WeightsArgs,
+    },
+    /// Check the actual fee of a transaction against the fee token transfer which paid it.
+    Reconcile {
+        /// The transaction hash.
+        #[arg(
+            short,
+            long,
+            value_name = "TX_HASH",
+            required_unless_present = "transaction_file"
+        )]
+        tx_hash: Option<String>,
+        /// The network gateway URL.
+        #[arg(long, value_name = "GATEWAY_URL", default_value = DEFAULT_SOURCE_NETWORK_GATEWAY_URL)]
+        gateway_url: String,
+        /// File containing the transaction receipt.
+        /// If provided, overrides `tx_hash`, and the sender is only checked if `tx_hash` is provided too.
+        #[arg(long, value_name = "FILE")]
+        transaction_file: Option<String>,
+        /// File containing the block which included the transaction, to check the fee was paid to its sequencer.
+        /// If not provided, the block is queried from `gateway_url`.
+        #[arg(long, value_name = "FILE")]
+        block_file: Option<String>,
     },
     /// Output a recap of used resources
     // TODO: Ideally find a way to have either `tx_hash || transaction_file` as mandatory args
@@ -81,49 +280,425 @@ pub enum FeesSubCommands {
         )]
         transaction_file: Option<String>,
 
-        #[arg(long, help="Overrides the default steps weight", default_value = DEFAULT_STEPS_WEIGHT)]
-        steps_weight: f32,
-
-        #[arg(long, help="Overrides the default pedersen weight", default_value = DEFAULT_PEDERSEN_WEIGHT)]
-        pedersen_weight: f32,
-
-        #[arg(long, help="Overrides the default range_check weight", default_value = DEFAULT_RANGE_CHECK_WEIGHT)]
-        range_check_weight: f32,
+        #[arg(
+            long,
+            help = "File containing the block which included the transaction. Overrides querying it from `gateway_url`"
+        )]
+        block_file: Option<String>,
 
-        #[arg(long, help="Overrides the default ecdsa weight", default_value = DEFAULT_ECDSA_WEIGHT)]
-        ecdsa_weight: f32,
+        #[arg(long, value_enum, help = "The unit to display the actual fee in")]
+        unit: Option<Unit>,
 
-        #[arg(long, help="Overrides the default bitwise weight", default_value = DEFAULT_BITWISE_WEIGHT)]
-        bitwise_weight: f32,
+        #[arg(
+            long,
+            help = "Query the USD price of the fee token, even when the receipt is read from a file"
+        )]
+        usd: bool,
 
-        #[arg(long, help="Overrides the default ec_op weight", default_value = DEFAULT_EC_OP_WEIGHT)]
-        ec_op_weight: f32,
+        #[command(flatten)]
+        weights: WeightsArgs,
 
-        #[arg(long, help = "Overrides the step count from the transaction receipt")]
-        steps: Option<String>,
+        #[arg(
+            long,
+            value_parser = parse_resource_count,
+            help = "Overrides the step count from the transaction receipt"
+        )]
+        steps: Option<f32>,
 
         #[arg(
             long,
+            value_parser = parse_resource_count,
             help = "Overrides the pedersen count from the transaction receipt"
         )]
-        pedersen: Option<String>,
+        pedersen: Option<f32>,
 
         #[arg(
             long,
+            value_parser = parse_resource_count,
             help = "Overrides the range_check count from the transaction receipt"
         )]
-        range_check: Option<String>,
+        range_check: Option<f32>,
 
-        #[arg(long, help = "Overrides the ecdsa count from the transaction receipt")]
-        ecdsa: Option<String>,
+        #[arg(
+            long,
+            value_parser = parse_resource_count,
+            help = "Overrides the ecdsa count from the transaction receipt"
+        )]
+        ecdsa: Option<f32>,
 
         #[arg(
             long,
+            value_parser = parse_resource_count,
             help = "Overrides the bitwise count from the transaction receipt"
         )]
-        bitwise: Option<String>,
+        bitwise: Option<f32>,
+
+        #[arg(
+            long,
+            value_parser = parse_resource_count,
+            help = "Overrides the ec_op count from the transaction receipt"
+        )]
+        ec_op: Option<f32>,
 
-        #[arg(long, help = "Overrides the ec_op count from the transaction receipt")]
-        ec_op: Option<String>,
+        #[arg(
+            long,
+            value_name = "RESOURCE=FACTOR",
+            help = "Scales a resource count, e.g. `pedersen=0.6`, and compares the fee before and after"
+        )]
+        scale: Vec<ResourceScale>,
+
+        #[arg(
+            long,
+            value_name = "NAME:RESOURCE=FACTOR,...",
+            help = "A named set of scales, e.g. `cheaper-hash:pedersen=0.6,steps=0.9`, compared with the others"
+        )]
+        scenario: Vec<Scenario>,
+
+        #[arg(long, help = "Lists the events emitted by the transaction, by name")]
+        events: bool,
+
+        #[arg(
+            long = "abi-file",
+            value_name = "FILE",
+            help = "File containing a contract class or ABI, to name the events"
+        )]
+        abi_files: Vec<String>,
+
+        #[arg(
+            long,
+            requires = "events",
+            help = "Queries the class of every event emitter, to name the events"
+        )]
+        fetch_abis: bool,
     },
 }
+
+/// The weight of each resource, relative to gas.
+#[derive(Args, Debug)]
+pub struct WeightsArgs {
+    #[arg(long, help="Overrides the default steps weight", default_value = DEFAULT_STEPS_WEIGHT)]
+    pub steps_weight: f32,
+
+    #[arg(long, help="Overrides the default pedersen weight", default_value = DEFAULT_PEDERSEN_WEIGHT)]
+    pub pedersen_weight: f32,
+
+    #[arg(long, help="Overrides the default range_check weight", default_value = DEFAULT_RANGE_CHECK_WEIGHT)]
+    pub range_check_weight: f32,
+
+    #[arg(long, help="Overrides the default ecdsa weight", default_value = DEFAULT_ECDSA_WEIGHT)]
+    pub ecdsa_weight: f32,
+
+    #[arg(long, help="Overrides the default bitwise weight", default_value = DEFAULT_BITWISE_WEIGHT)]
+    pub bitwise_weight: f32,
+
+    #[arg(long, help="Overrides the default ec_op weight", default_value = DEFAULT_EC_OP_WEIGHT)]
+    pub ec_op_weight: f32,
+}
+
+impl WeightsArgs {
+    pub fn to_weights(&self) -> Weights {
+        Weights::new(
+            "weight",
+            self.steps_weight,
+            self.pedersen_weight,
+            self.range_check_weight,
+            self.ecdsa_weight,
+            self.bitwise_weight,
+            self.ec_op_weight,
+        )
+    }
+}
+
+/// Account related commands.
+#[derive(Parser, Debug)]
+pub struct AccountCommands {
+    /// Account related subcommands.
+    #[command(subcommand)]
+    pub command: AccountSubCommands,
+}
+
+/// Account related subcommands.
+#[derive(Subcommand, Debug)]
+pub enum AccountSubCommands {
+    /// Export the fees an account paid over a range of blocks or days.
+    /// Every block of the range is scanned, so ranges are capped at 10,000 blocks.
+    History {
+        /// The account address.
+        #[arg(long, value_name = "ADDRESS")]
+        account: String,
+        /// The network to query.
+        #[arg(long, value_enum, default_value = "mainnet")]
+        network: Network,
+        /// The network gateway URL.
+        /// If provided, overrides the network gateway URL.
+        #[arg(long, value_name = "GATEWAY_URL")]
+        gateway_url: Option<String>,
+        /// The first block of the range.
+        #[arg(
+            long,
+            value_name = "BLOCK_NUMBER",
+            required_unless_present = "from_date",
+            conflicts_with = "from_date",
+            requires = "to"
+        )]
+        from: Option<u64>,
+        /// The last block of the range, included.
+        #[arg(long, value_name = "BLOCK_NUMBER", conflicts_with = "to_date")]
+        to: Option<u64>,
+        /// The first day of the range, as `YYYY-MM-DD` in UTC.
+        #[arg(long, value_name = "DATE", requires = "to_date")]
+        from_date: Option<String>,
+        /// The last day of the range, included, as `YYYY-MM-DD` in UTC.
+        #[arg(long, value_name = "DATE")]
+        to_date: Option<String>,
+        /// Don't query the historical USD price of the fee token.
+        #[arg(long)]
+        no_prices: bool,
+        /// The unit to display the fees in, instead of their token.
+        /// Fees paid in the other token are kept in their token.
+        #[arg(long, value_enum, conflicts_with = "no_prices")]
+        unit: Option<Unit>,
+        /// The output format.
+        #[arg(long, value_enum, default_value = "table")]
+        format: ReportFormat,
+        /// The file to write the output to.
+        /// If not provided, the output is printed.
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+    },
+}
+
+/// Analysis commands.
+#[derive(Parser, Debug)]
+pub struct AnalyzeCommands {
+    /// Analysis subcommands.
+    #[command(subcommand)]
+    pub command: AnalyzeSubCommands,
+}
+
+/// Analysis subcommands.
+#[derive(Subcommand, Debug)]
+pub enum AnalyzeSubCommands {
+    /// Rank the contracts invoked over a range of blocks by the fees and resources they consumed.
+    Contracts {
+        /// The network to query.
+        #[arg(long, value_enum, default_value = "mainnet")]
+        network: Network,
+        /// The network gateway URL.
+        /// If provided, overrides the network gateway URL.
+        #[arg(long, value_name = "GATEWAY_URL")]
+        gateway_url: Option<String>,
+        /// The first block of the range.
+        #[arg(long, value_name = "BLOCK_NUMBER")]
+        from: u64,
+        /// The last block of the range, included.
+        #[arg(long, value_name = "BLOCK_NUMBER")]
+        to: u64,
+        /// The number of contracts to show.
+        /// If not provided, all the contracts are shown.
+        #[arg(long, value_name = "N")]
+        top: Option<usize>,
+        /// The output format.
+        #[arg(long, value_enum, default_value = "table")]
+        format: ReportFormat,
+        /// The file to write the output to.
+        /// If not provided, the output is printed.
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+        #[command(flatten)]
+        weights: WeightsArgs,
+    },
+    /// List the most expensive transactions of a range of blocks, by fee and by resource,
+    /// and flag the ones paying much more than usual for their entrypoint.
+    Transactions {
+        /// The network to query.
+        #[arg(long, value_enum, default_value = "mainnet")]
+        network: Network,
+        /// The network gateway URL.
+        /// If provided, overrides the network gateway URL.
+        #[arg(long, value_name = "GATEWAY_URL")]
+        gateway_url: Option<String>,
+        /// The first block of the range.
+        #[arg(long, value_name = "BLOCK_NUMBER")]
+        from: u64,
+        /// The last block of the range, included.
+        #[arg(long, value_name = "BLOCK_NUMBER")]
+        to: u64,
+        /// The number of transactions to show per ranking.
+        #[arg(long, value_name = "N", default_value = "10")]
+        top: usize,
+        /// How many times the median fee of its contract and entrypoint a transaction must pay
+        /// to be flagged as an outlier.
+        #[arg(
+            long,
+            value_name = "FACTOR",
+            default_value = "5",
+            value_parser = parse_outlier_factor
+        )]
+        outlier_factor: f64,
+        /// The number of transactions an entrypoint needs for its outliers to be flagged.
+        #[arg(long, value_name = "N", default_value = "5")]
+        min_samples: usize,
+        /// Contract class or ABI files, to name the entrypoints.
+        #[arg(long = "abi-file", value_name = "FILE")]
+        abi_files: Vec<String>,
+        /// The file to write the output to.
+        /// If not provided, the output is printed.
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Report what the blocks of a range used against their capacity, and what their transactions paid.
+    Blocks {
+        /// The network to query.
+        #[arg(long, value_enum, default_value = "mainnet")]
+        network: Network,
+        /// The network gateway URL.
+        /// If provided, overrides the network gateway URL.
+        #[arg(long, value_name = "GATEWAY_URL")]
+        gateway_url: Option<String>,
+        /// The first block of the range.
+        #[arg(long, value_name = "BLOCK_NUMBER")]
+        from: u64,
+        /// The last block of the range, included.
+        #[arg(long, value_name = "BLOCK_NUMBER")]
+        to: u64,
+        /// The number of Cairo steps a block can hold, an approximation by default.
+        /// The capacity of each builtin is approximated from it and from the weights.
+        #[arg(long, value_name = "STEPS", default_value_t = DEFAULT_BLOCK_MAX_STEPS)]
+        block_max_steps: f32,
+        /// The output format.
+        #[arg(long, value_enum, default_value = "table")]
+        format: ReportFormat,
+        /// The file to write the output to.
+        /// If not provided, the output is printed.
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+        #[command(flatten)]
+        weights: WeightsArgs,
+    },
+}
+
+/// Gas price related commands.
+#[derive(Parser, Debug)]
+pub struct GasPriceCommands {
+    /// Gas price related subcommands.
+    #[command(subcommand)]
+    pub command: GasPriceSubCommands,
+}
+
+/// Gas price related subcommands.
+#[derive(Subcommand, Debug)]
+pub enum GasPriceSubCommands {
+    /// Export the gas prices of a range of blocks.
+    History {
+        /// The network to query.
+        #[arg(long, value_enum, default_value = "mainnet")]
+        network: Network,
+        /// The network gateway URL.
+        /// If provided, overrides the network gateway URL.
+        #[arg(long, value_name = "GATEWAY_URL")]
+        gateway_url: Option<String>,
+        /// The first block of the range.
+        #[arg(long, value_name = "BLOCK_NUMBER")]
+        from: u64,
+        /// The last block of the range, included.
+        #[arg(long, value_name = "BLOCK_NUMBER")]
+        to: u64,
+        /// The number of blocks between two samples.
+        #[arg(long, value_name = "N", default_value = "1")]
+        step: u64,
+        /// The output format.
+        #[arg(long, value_enum, default_value = "chart")]
+        format: HistoryFormat,
+        /// The unit the chart plots gas prices in.
+        #[arg(long, value_enum, default_value = "wei")]
+        unit: FeeUnit,
+        /// The file to write the output to.
+        /// If not provided, the output is printed.
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Watch the gas price of the latest block.
+    Watch {
+        /// The network to query.
+        #[arg(long, value_enum, default_value = "mainnet")]
+        network: Network,
+        /// The network gateway URL.
+        /// If provided, overrides the network gateway URL.
+        #[arg(long, value_name = "GATEWAY_URL")]
+        gateway_url: Option<String>,
+        /// The number of seconds between two polls.
+        #[arg(long, value_name = "SECONDS", default_value = "30")]
+        interval: u64,
+        /// The watched fee market dimension.
+        #[arg(long, value_enum, default_value = "l1-gas")]
+        dimension: GasDimension,
+        /// The unit the gas price and thresholds are denominated in.
+        #[arg(long, value_enum, default_value = "wei")]
+        unit: FeeUnit,
+        /// Fire the hooks when the gas price drops below this price.
+        #[arg(long, value_name = "PRICE", value_parser = parse_u256, conflicts_with = "above")]
+        below: Option<U256>,
+        /// Fire the hooks when the gas price rises above this price.
+        #[arg(long, value_name = "PRICE", value_parser = parse_u256)]
+        above: Option<U256>,
+        /// A shell command to run when the threshold is crossed.
+        #[arg(long, value_name = "COMMAND")]
+        exec: Option<String>,
+        /// A URL to POST the gas prices to when the threshold is crossed.
+        #[arg(long, value_name = "URL")]
+        webhook: Option<String>,
+        /// Stop watching once the threshold is crossed.
+        /// Always the case when no hook is provided.
+        #[arg(long)]
+        exit: bool,
+    },
+}
+
+/// Parses a decimal or `0x` prefixed hexadecimal amount.
+pub fn parse_u256(s: &str) -> Result<U256, String> {
+    parse_u256_str(s).map_err(|e| format!("Invalid amount {s}: {e}"))
+}
+
+/// Parses a resource count, which must be a non-negative number.
+pub fn parse_resource_count(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(count) if count.is_finite() && count >= 0.0 => Ok(count),
+        _ => Err(format!("Invalid count {s}, expected a non-negative number")),
+    }
+}
+
+/// Parses an outlier factor, which must be a positive number.
+pub fn parse_outlier_factor(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(factor) if factor.is_finite() && factor > 0.0 => Ok(factor),
+        _ => Err(format!("Invalid factor {s}, expected a positive number")),
+    }
+}
+
+/// Parses gas prices, either a single L1 gas price or a list of `<dimension>=<price>`.
+/// Prices apply to both fee units.
+pub fn parse_gas_prices(s: &str) -> Result<GasPrices, String> {
+    let price = |s: &str| {
+        parse_u256(s).map(|price| ResourcePrice {
+            price_in_wei: price,
+            price_in_fri: price,
+        })
+    };
+    if !s.contains('=') {
+        return Ok(GasPrices {
+            l1_gas: price(s)?,
+            ..Default::default()
+        });
+    }
+    let mut gas_prices = GasPrices::default();
+    for pair in s.split(',') {
+        match pair.split_once('=') {
+            Some(("l1_gas", value)) => gas_prices.l1_gas = price(value)?,
+            Some(("l1_data_gas", value)) => gas_prices.l1_data_gas = price(value)?,
+            Some(("l2_gas", value)) => gas_prices.l2_gas = price(value)?,
+            _ => return Err(format!("Invalid gas price {pair}")),
+        }
+    }
+    Ok(gas_prices)
+}
@@ -1,34 +1,93 @@
 use ethers::types::U256;
 
-const URL: &'static str =
-    "https://api.coingecko.com/api/v3/simple/price?ids=ethereum&vs_currencies=usd";
+use crate::date::date_from_timestamp;
+use crate::model::FeeUnit;
+use crate::setting::Setting;
 
-fn get_eth_price() -> Option<f32> {
-    let body = reqwest::blocking::get(URL).ok()?.text().ok()?;
+/// The default price oracle, coingecko.
+pub const DEFAULT_PRICE_ORACLE_URL: &str = "https://api.coingecko.com/api/v3";
+
+static PRICE_ORACLE_URL: Setting<Option<String>> = Setting::new(None);
+
+/// Sets the base URL of the price oracle, which must serve the coingecko API.
+pub fn set_price_oracle_url(url: &str) {
+    PRICE_ORACLE_URL.set(Some(url.trim_end_matches('/').to_string()));
+}
+
+/// Returns the base URL of the price oracle.
+pub fn price_oracle_url() -> String {
+    PRICE_ORACLE_URL
+        .get()
+        .unwrap_or_else(|| DEFAULT_PRICE_ORACLE_URL.to_string())
+}
+
+/// Returns the USD price of the token paying fees in `unit`, from the price oracle.
+pub fn get_token_price(unit: FeeUnit) -> Option<f32> {
+    let url = format!(
+        "{}/simple/price?ids=ethereum,starknet&vs_currencies=usd",
+        price_oracle_url()
+    );
+    let body = crate::http_get(&url).ok()?;
 
     let parsed = json::parse(&body).ok()?;
-    let num = parsed["ethereum"]["usd"].as_f32()?;
+    let num = parsed[coingecko_id(unit)]["usd"].as_f32()?;
 
     Some(num)
 }
 
+/// Returns the USD price of the token paying fees in `unit` on the UTC day of a timestamp, from the price oracle.
+pub fn get_historical_token_price(unit: FeeUnit, timestamp: u64) -> Option<f32> {
+    let (year, month, day) = date_from_timestamp(timestamp);
+    let url = format!(
+        "{}/coins/{}/history?date={day:02}-{month:02}-{year:04}&localization=false",
+        price_oracle_url(),
+        coingecko_id(unit)
+    );
+    let body = crate::http_get(&url).ok()?;
+
+    let parsed = json::parse(&body).ok()?;
+    parsed["market_data"]["current_price"]["usd"].as_f32()
+}
+
+/// Returns the coingecko id of the token paying fees in `unit`.
+fn coingecko_id(unit: FeeUnit) -> &'static str {
+    match unit {
+        FeeUnit::Wei => "ethereum",
+        FeeUnit::Fri => "starknet",
+    }
+}
+
 /// Returns the string corresponding to the dollar cost in dollars (USD).
-/// The oracle is coingecko (see [`URL`]).
+/// The oracle is coingecko (see [`set_price_oracle_url`]).
 /// Output is formatted with a precision of 4
 pub fn format_dollar_cost(fee: U256) -> String {
-    let eth_price = get_eth_price();
-    let eth_price = match eth_price {
-        None => return String::from("could not display USD estimate: failed to get eth price"),
+    format_token_dollar_cost(fee, FeeUnit::Wei)
+}
+
+/// Same as [`format_dollar_cost`] for a fee denominated in `unit`.
+pub fn format_token_dollar_cost(fee: U256, unit: FeeUnit) -> String {
+    format_dollar_cost_at(fee, unit, get_token_price(unit))
+}
+
+/// Same as [`format_token_dollar_cost`] with an already known token price.
+pub fn format_dollar_cost_at(fee: U256, unit: FeeUnit, token_price: Option<f32>) -> String {
+    let token_price = match token_price {
+        None => {
+            return format!(
+                "could not display USD estimate: failed to get {} price",
+                unit.token_symbol().to_lowercase()
+            )
+        }
         Some(p) => p,
     };
 
-    let fee_in_eth = ethers::utils::format_units(fee, "ether");
-    let fee_in_eth = match fee_in_eth {
+    let fee_in_token = ethers::utils::format_units(fee, "ether");
+    let fee_in_token = match fee_in_token {
         Err(_) => return String::from("could not display USD estimate: failed to convert fees"),
         Ok(fee) => fee,
     };
 
-    let float = fee_in_eth.parse::<f32>();
+    let float = fee_in_token.parse::<f32>();
     let float = match float {
         Err(_) => {
             return String::from("could not display USD estimate: failed to parse the number")
@@ -36,6 +95,6 @@ pub fn format_dollar_cost(fee: U256) -> String {
         Ok(f) => f,
     };
 
-    let dollar_fee = float * eth_price;
+    let dollar_fee = float * token_price;
     format!("${:.4} USD", dollar_fee)
 }
@@ -0,0 +1,142 @@
+use eyre::Result;
+
+pub const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Returns the UTC `(year, month, day)` of a Unix timestamp.
+pub fn date_from_timestamp(timestamp: u64) -> (u64, u64, u64) {
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = timestamp / SECONDS_PER_DAY + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Returns the Unix timestamp of the start of a UTC day.
+pub fn timestamp_from_date(year: u64, month: u64, day: u64) -> u64 {
+    // See http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = year - u64::from(month <= 2);
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let shifted_month = (month + 9) % 12;
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    (era * 146_097 + day_of_era - 719_468) * SECONDS_PER_DAY
+}
+
+/// Returns the number of days in a month, accounting for leap years.
+pub fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parses a `YYYY-MM-DD` date, after 1970, into the timestamp of the start of that UTC day.
+///
+/// # Example
+///
+/// ```
+/// use howmuch_rs::date::{format_date, parse_date};
+///
+/// let timestamp = parse_date("2022-11-14").unwrap();
+/// assert_eq!(timestamp, 1668384000);
+/// assert_eq!(format_date(1668438015), "2022-11-14");
+/// assert!(parse_date("2024-02-29").is_ok());
+/// assert!(parse_date("2023-02-29").is_err());
+/// assert!(parse_date("2023-13-01").is_err());
+/// ```
+pub fn parse_date(s: &str) -> Result<u64> {
+    let parts: Vec<u64> = s
+        .split('-')
+        .map(|part| part.parse::<u64>())
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| eyre::eyre!("Invalid date {s}, expected YYYY-MM-DD"))?;
+    match parts[..] {
+        [year, month @ 1..=12, day]
+            if year >= 1970 && (1..=days_in_month(year, month)).contains(&day) =>
+        {
+            Ok(timestamp_from_date(year, month, day))
+        }
+        _ => Err(eyre::eyre!("Invalid date {s}, expected YYYY-MM-DD")),
+    }
+}
+
+/// Formats the UTC date of a Unix timestamp as `YYYY-MM-DD`.
+pub fn format_date(timestamp: u64) -> String {
+    let (year, month, day) = date_from_timestamp(timestamp);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leap_years() {
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2023, 2), 28);
+        assert_eq!(days_in_month(2000, 2), 29);
+        assert_eq!(days_in_month(2100, 2), 28);
+        assert!(parse_date("2024-02-29").is_ok());
+        assert!(parse_date("2000-02-29").is_ok());
+        assert!(parse_date("2023-02-29").is_err());
+        assert!(parse_date("2100-02-29").is_err());
+    }
+
+    #[test]
+    fn month_ends() {
+        assert_eq!(parse_date("2023-01-31").unwrap(), 1675123200);
+        assert_eq!(parse_date("2023-02-01").unwrap(), 1675209600);
+        assert_eq!(parse_date("2023-12-31").unwrap(), 1703980800);
+        assert_eq!(parse_date("2024-01-01").unwrap(), 1704067200);
+        assert!(parse_date("2023-04-30").is_ok());
+        assert!(parse_date("2023-04-31").is_err());
+        assert!(parse_date("2023-02-31").is_err());
+    }
+
+    #[test]
+    fn invalid_dates() {
+        for date in [
+            "2023-13-01",
+            "2023-00-10",
+            "2023-01-00",
+            "1969-12-31",
+            "2023-01",
+            "2023-01-01-01",
+            "2023/01/01",
+            "",
+        ] {
+            assert!(parse_date(date).is_err(), "{date}");
+        }
+    }
+
+    #[test]
+    fn dates_round_trip() {
+        for date in [
+            "1970-01-01",
+            "2022-11-14",
+            "2024-02-29",
+            "2024-03-01",
+            "2099-12-31",
+        ] {
+            assert_eq!(format_date(parse_date(date).unwrap()), date);
+        }
+        assert_eq!(date_from_timestamp(1709251199), (2024, 2, 29));
+        assert_eq!(date_from_timestamp(1709251200), (2024, 3, 1));
+    }
+}
@@ -0,0 +1,264 @@
+use std::fmt;
+
+use ethers::types::U256;
+use eyre::Result;
+
+use crate::currencies::{format_dollar_cost_at, price_oracle_url};
+use crate::gas_price::PriceStat;
+use crate::model::{FeeUnit, GasConsumed, GasPrices, StaticFee};
+use crate::units::{format_amount, Unit};
+use crate::{compute_actual_tx_fee, compute_tx_fee};
+
+/// How the source fee is carried over to another gas price.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Derivation {
+    /// The receipt reports the gas consumed on each fee market dimension,
+    /// each of them is priced on its own.
+    Gas(GasConsumed),
+    /// The gas units are derived from the source fee and the source block L1 gas price.
+    StaticFee {
+        static_fee: StaticFee,
+        source_block_number: Option<u64>,
+    },
+}
+
+impl Derivation {
+    /// Returns the fee at the given gas prices.
+    pub fn fee_at(&self, gas_prices: &GasPrices, unit: FeeUnit) -> Result<U256> {
+        match self {
+            Derivation::Gas(gas_consumed) => compute_tx_fee(gas_consumed, gas_prices, unit),
+            Derivation::StaticFee { static_fee, .. } => {
+                compute_actual_tx_fee(*static_fee, gas_prices.l1_gas.in_unit(unit))
+            }
+        }
+    }
+}
+
+/// Where the destination gas prices come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PriceSource {
+    /// A block queried from a network.
+    Block {
+        block_number: Option<u64>,
+        network_gateway_url: String,
+    },
+    /// A block read from a file.
+    BlockFile(String),
+    /// Gas prices provided as is.
+    Literal,
+    /// A statistic over a window of blocks queried from a network.
+    Window {
+        stat: PriceStat,
+        blocks: usize,
+        network_gateway_url: String,
+    },
+}
+
+impl fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceSource::Block {
+                block_number: Some(block_number),
+                network_gateway_url,
+            } => write!(f, "block {block_number} on {network_gateway_url}"),
+            PriceSource::Block {
+                block_number: None,
+                network_gateway_url,
+            } => write!(f, "latest block on {network_gateway_url}"),
+            PriceSource::BlockFile(filename) => write!(f, "block file {filename}"),
+            PriceSource::Literal => write!(f, "provided gas price"),
+            PriceSource::Window {
+                stat,
+                blocks,
+                network_gateway_url,
+            } => write!(f, "{stat:?} over {blocks} blocks on {network_gateway_url}"),
+        }
+    }
+}
+
+/// A fee estimate, along with every value it was derived from.
+#[derive(Debug, Clone)]
+pub struct Estimate {
+    /// The unit both the source and the destination fees are denominated in.
+    pub fee_unit: FeeUnit,
+    /// The actual fee of the transaction on the source network.
+    pub source_fee: U256,
+    /// How the source fee is carried over to the destination gas prices.
+    pub derivation: Derivation,
+    /// The destination gas prices.
+    pub destination_gas_prices: GasPrices,
+    /// Where the destination gas prices come from.
+    pub price_source: PriceSource,
+    /// The estimated fee on the destination network.
+    pub destination_fee: U256,
+    /// The fees at the lowest and highest gas prices of the window, if any.
+    pub fee_range: Option<(U256, U256)>,
+    /// The unit fees are displayed in.
+    pub display_unit: Unit,
+    /// Whether the dollar cost is displayed.
+    pub usd: bool,
+    /// The USD price of the fee token, if queried.
+    pub token_price: Option<f32>,
+    /// Anything which makes the estimate less reliable.
+    pub caveats: Vec<String>,
+}
+
+impl Estimate {
+    /// Returns the destination fee in the display unit, along with its dollar cost and fee range when relevant.
+    /// # Errors
+    /// If the fee can't be converted to the display unit, such as wei to STRK, or USD without a token price.
+    pub fn try_render(&self) -> Result<String> {
+        let mut rendered = self.format_token(self.destination_fee)?;
+        if self.usd && self.display_unit != Unit::Usd {
+            rendered.push_str(&format!(
+                " ({})",
+                format_dollar_cost_at(self.destination_fee, self.fee_unit, self.token_price)
+            ));
+        }
+        if let (Some((min_fee, max_fee)), PriceSource::Window { stat, blocks, .. }) =
+            (self.fee_range, &self.price_source)
+        {
+            rendered.push_str(&format!(
+                " [{stat:?} over {blocks} blocks, range: {} - {}]",
+                self.format_token(min_fee)?,
+                self.format_token(max_fee)?
+            ));
+        }
+        Ok(rendered)
+    }
+
+    /// Returns the readable derivation of the estimate, with every number labelled with its unit.
+    pub fn explain(&self) -> Result<String> {
+        let unit = self.fee_unit.name();
+        let mut lines = vec![(
+            "Source fee",
+            format!(
+                "{} {unit} ({})",
+                self.source_fee,
+                self.format_token(self.source_fee)?
+            ),
+        )];
+        match &self.derivation {
+            Derivation::Gas(gas_consumed) => {
+                let gas = [
+                    (
+                        "L1 gas",
+                        gas_consumed.l1_gas,
+                        self.destination_gas_prices.l1_gas,
+                    ),
+                    (
+                        "L1 data gas",
+                        gas_consumed.l1_data_gas,
+                        self.destination_gas_prices.l1_data_gas,
+                    ),
+                    (
+                        "L2 gas",
+                        gas_consumed.l2_gas,
+                        self.destination_gas_prices.l2_gas,
+                    ),
+                ];
+                let consumed: Vec<String> = gas
+                    .iter()
+                    .map(|(name, consumed, _)| format!("{name}: {consumed} gas"))
+                    .collect();
+                let prices: Vec<String> = gas
+                    .iter()
+                    .map(|(name, _, price)| {
+                        format!("{name}: {} {unit}/gas", price.in_unit(self.fee_unit))
+                    })
+                    .collect();
+                let terms: Vec<String> = gas
+                    .iter()
+                    .map(|(_, consumed, price)| {
+                        format!(
+                            "{consumed} gas × {} {unit}/gas",
+                            price.in_unit(self.fee_unit)
+                        )
+                    })
+                    .collect();
+                lines.push(("Gas consumed", consumed.join(", ")));
+                lines.push((
+                    "Destination gas price",
+                    format!("{} ({})", prices.join(", "), self.price_source),
+                ));
+                lines.push((
+                    "Destination fee",
+                    format!(
+                        "{} = {} {unit} ({})",
+                        terms.join(" + "),
+                        self.destination_fee,
+                        self.format_token(self.destination_fee)?
+                    ),
+                ));
+            }
+            Derivation::StaticFee {
+                static_fee,
+                source_block_number,
+            } => {
+                let source_block = match source_block_number {
+                    Some(block_number) => format!("block {block_number}"),
+                    None => String::from("pending block"),
+                };
+                let destination_gas_price =
+                    self.destination_gas_prices.l1_gas.in_unit(self.fee_unit);
+                lines.push((
+                    "Source gas price",
+                    format!("{} {unit}/gas ({source_block})", static_fee.gas_price),
+                ));
+                lines.push(("Derived gas", static_fee.to_string()));
+                lines.push((
+                    "Destination gas price",
+                    format!("{destination_gas_price} {unit}/gas ({})", self.price_source),
+                ));
+                lines.push((
+                    "Destination fee",
+                    format!(
+                        "{static_fee} × {destination_gas_price} {unit}/gas = {} {unit} ({})",
+                        self.destination_fee,
+                        self.format_token(self.destination_fee)?
+                    ),
+                ));
+            }
+        }
+        if let Some((min_fee, max_fee)) = self.fee_range {
+            lines.push((
+                "Fee range",
+                format!(
+                    "{min_fee} {unit} - {max_fee} {unit} ({} - {})",
+                    self.format_token(min_fee)?,
+                    self.format_token(max_fee)?
+                ),
+            ));
+        }
+        if self.usd {
+            let symbol = self.fee_unit.token_symbol();
+            let token_price = match self.token_price {
+                Some(token_price) => format!("{token_price} USD/{symbol}"),
+                None => String::from("unavailable"),
+            };
+            lines.push((
+                "Token price",
+                format!("{token_price} (price oracle {})", price_oracle_url()),
+            ));
+            lines.push((
+                "Dollar cost",
+                format_dollar_cost_at(self.destination_fee, self.fee_unit, self.token_price),
+            ));
+        }
+        let mut explanation: String = lines
+            .iter()
+            .map(|(label, value)| format!("{:<22} {value}\n", format!("{label}:")))
+            .collect();
+        if !self.caveats.is_empty() {
+            explanation.push_str("Caveats:\n");
+            for caveat in &self.caveats {
+                explanation.push_str(&format!("  - {caveat}\n"));
+            }
+        }
+        Ok(explanation)
+    }
+
+    fn format_token(&self, amount: U256) -> Result<String> {
+        format_amount(amount, self.fee_unit, self.display_unit, self.token_price)
+    }
+}
@@ -0,0 +1,547 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use ethers::types::U256;
+use eyre::Result;
+use log::{debug, warn};
+use serde::Serialize;
+
+use crate::model::{Block, FeeUnit, GasDimension, GasPrices, ResourcePrice};
+use crate::query_block;
+
+/// Maximum number of blocks fetched for a window.
+pub const MAX_WINDOW_BLOCKS: u64 = 1000;
+
+/// Statistic used to summarize gas prices over a window of blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PriceStat {
+    Min,
+    Median,
+    Mean,
+    P90,
+    Max,
+}
+
+/// A window of blocks, ending at a given block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockWindow {
+    /// The last `n` blocks.
+    Blocks(u64),
+    /// The blocks produced during the last `n` seconds.
+    Seconds(u64),
+}
+
+impl FromStr for BlockWindow {
+    type Err = String;
+
+    /// Parses either a number of blocks (`100`) or a duration (`90s`, `30m`, `2h`, `1d`).
+    /// A number of blocks can't exceed [`MAX_WINDOW_BLOCKS`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid window: {s}, expected blocks (100) or a duration (30m)");
+        let (value, multiplier) = match s.char_indices().last() {
+            Some((i, 's')) => (&s[..i], Some(1)),
+            Some((i, 'm')) => (&s[..i], Some(60)),
+            Some((i, 'h')) => (&s[..i], Some(3600)),
+            Some((i, 'd')) => (&s[..i], Some(86400)),
+            _ => (s, None),
+        };
+        let value: u64 = value.parse().map_err(|_| invalid())?;
+        if value == 0 {
+            return Err(invalid());
+        }
+        match multiplier {
+            None if value > MAX_WINDOW_BLOCKS => Err(format!(
+                "Invalid window: {s}, over the maximum of {MAX_WINDOW_BLOCKS} blocks"
+            )),
+            None => Ok(BlockWindow::Blocks(value)),
+            Some(multiplier) => value
+                .checked_mul(multiplier)
+                .map(BlockWindow::Seconds)
+                .ok_or_else(|| format!("Invalid window: {s}, the duration is too long")),
+        }
+    }
+}
+
+/// A gas price statistic computed over a window of blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceWindow {
+    pub stat: PriceStat,
+    pub window: BlockWindow,
+}
+
+/// Gas price statistics over a set of blocks, for every fee market dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasPriceStats {
+    pub blocks: usize,
+    pub min: GasPrices,
+    pub median: GasPrices,
+    pub mean: GasPrices,
+    pub p90: GasPrices,
+    pub max: GasPrices,
+}
+
+impl GasPriceStats {
+    /// Computes the statistics of the given gas prices.
+    /// Each dimension and unit is summarized independently.
+    /// # Errors
+    /// If no gas prices are given.
+    pub fn from_gas_prices(gas_prices: &[GasPrices]) -> Result<Self> {
+        if gas_prices.is_empty() {
+            return Err(eyre::eyre!("Cannot compute statistics of an empty window"));
+        }
+        let mut columns: [Vec<U256>; 6] = Default::default();
+        for prices in gas_prices {
+            for (column, price) in columns.iter_mut().zip(components(prices)) {
+                column.push(price);
+            }
+        }
+        for column in columns.iter_mut() {
+            column.sort();
+        }
+        let stat = |f: fn(&[U256]) -> U256| {
+            from_components([
+                f(&columns[0]),
+                f(&columns[1]),
+                f(&columns[2]),
+                f(&columns[3]),
+                f(&columns[4]),
+                f(&columns[5]),
+            ])
+        };
+        Ok(Self {
+            blocks: gas_prices.len(),
+            min: stat(|sorted| sorted[0]),
+            median: stat(median),
+            mean: stat(mean),
+            p90: stat(|sorted| percentile(sorted, 90)),
+            max: stat(|sorted| sorted[sorted.len() - 1]),
+        })
+    }
+
+    /// Returns the gas prices matching the given statistic.
+    pub fn get(&self, stat: PriceStat) -> GasPrices {
+        match stat {
+            PriceStat::Min => self.min,
+            PriceStat::Median => self.median,
+            PriceStat::Mean => self.mean,
+            PriceStat::P90 => self.p90,
+            PriceStat::Max => self.max,
+        }
+    }
+}
+
+/// The gas prices of a block, at the time it was produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct GasPriceSample {
+    pub block_number: u64,
+    pub timestamp: u64,
+    pub gas_prices: GasPrices,
+}
+
+impl GasPriceSample {
+    /// Samples the gas prices of a block.
+    pub fn try_from_block(block: &Block) -> Result<Self> {
+        Ok(Self {
+            block_number: block.block_number()?,
+            timestamp: block.timestamp()?,
+            gas_prices: block.gas_prices()?,
+        })
+    }
+}
+
+/// Export format of a gas price history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum HistoryFormat {
+    Chart,
+    Csv,
+    Json,
+}
+
+/// Query the gas prices of a range of blocks.
+/// # Arguments
+/// * `from` - The first block of the range.
+/// * `to` - The last block of the range, included.
+/// * `step` - The number of blocks between two samples.
+/// * `network_gateway_url` - The network gateway URL.
+/// # Returns
+/// The gas prices, from the oldest block to the most recent one.
+pub fn query_gas_price_history(
+    from: u64,
+    to: u64,
+    step: u64,
+    network_gateway_url: &str,
+) -> Result<Vec<GasPriceSample>> {
+    if from > to {
+        return Err(eyre::eyre!("Invalid block range: {} > {}", from, to));
+    }
+    if step == 0 {
+        return Err(eyre::eyre!("Step must be greater than zero"));
+    }
+    (from..=to)
+        .step_by(step as usize)
+        .map(|number| {
+            debug!("querying block {} for gas price history", number);
+            GasPriceSample::try_from_block(&query_block(&number.to_string(), network_gateway_url)?)
+        })
+        .collect()
+}
+
+/// A gas price level which, once crossed, triggers the watch hooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threshold {
+    /// Crossed when the gas price drops below the given price.
+    Below(U256),
+    /// Crossed when the gas price rises above the given price.
+    Above(U256),
+}
+
+impl Threshold {
+    /// Returns whether the gas price is past the threshold.
+    pub fn is_crossed(&self, gas_price: U256) -> bool {
+        match self {
+            Threshold::Below(threshold) => gas_price < *threshold,
+            Threshold::Above(threshold) => gas_price > *threshold,
+        }
+    }
+}
+
+impl std::fmt::Display for Threshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Threshold::Below(threshold) => write!(f, "below {threshold}"),
+            Threshold::Above(threshold) => write!(f, "above {threshold}"),
+        }
+    }
+}
+
+/// An action fired when a watched gas price crosses its threshold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchHook {
+    /// A shell command, run with the `HOWMUCH_BLOCK_NUMBER` and `HOWMUCH_GAS_PRICE` variables set.
+    Command(String),
+    /// A URL the sample is POSTed to as JSON.
+    Webhook(String),
+}
+
+impl WatchHook {
+    /// Fires the hook for the given sample and watched gas price.
+    pub fn fire(&self, sample: &GasPriceSample, gas_price: U256) -> Result<()> {
+        match self {
+            WatchHook::Command(command) => {
+                let status = std::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .env("HOWMUCH_BLOCK_NUMBER", sample.block_number.to_string())
+                    .env("HOWMUCH_GAS_PRICE", gas_price.to_string())
+                    .status()?;
+                if !status.success() {
+                    return Err(eyre::eyre!("Hook command failed with {}", status));
+                }
+            }
+            WatchHook::Webhook(url) => {
+                reqwest::blocking::Client::new()
+                    .post(url)
+                    .header("content-type", "application/json")
+                    .body(serde_json::to_string(sample)?)
+                    .send()?
+                    .error_for_status()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Poll the latest block of a network and call `on_block` for every new block.
+/// Failing queries are logged and retried at the next interval.
+/// # Arguments
+/// * `network_gateway_url` - The network gateway URL.
+/// * `interval` - The time between two polls.
+/// * `on_block` - Called with the gas prices of every new block, returns whether to keep watching.
+pub fn watch_gas_price<F>(
+    network_gateway_url: &str,
+    interval: Duration,
+    mut on_block: F,
+) -> Result<()>
+where
+    F: FnMut(&GasPriceSample) -> Result<bool>,
+{
+    let mut last_block_number = None;
+    loop {
+        let sample = query_block("latest", network_gateway_url)
+            .and_then(|block| GasPriceSample::try_from_block(&block));
+        match sample {
+            Ok(sample) if Some(sample.block_number) != last_block_number => {
+                last_block_number = Some(sample.block_number);
+                if !on_block(&sample)? {
+                    return Ok(());
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!("failed to query the latest block: {}", e),
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+/// Formats a gas price history as CSV, with one column per dimension and unit.
+pub fn history_to_csv(samples: &[GasPriceSample]) -> String {
+    let mut csv = String::from(
+        "block_number,timestamp,l1_gas_price_in_wei,l1_gas_price_in_fri,\
+         l1_data_gas_price_in_wei,l1_data_gas_price_in_fri,l2_gas_price_in_wei,l2_gas_price_in_fri\n",
+    );
+    for sample in samples {
+        let prices = components(&sample.gas_prices).map(|price| price.to_string());
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            sample.block_number,
+            sample.timestamp,
+            prices.join(",")
+        ));
+    }
+    csv
+}
+
+/// Formats a gas price history as JSON.
+pub fn history_to_json(samples: &[GasPriceSample]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(samples)?)
+}
+
+/// Renders a gas price history as one sparkline per dimension, priced in `unit`.
+pub fn history_to_chart(samples: &[GasPriceSample], unit: FeeUnit) -> String {
+    let (first, last) = match (samples.first(), samples.last()) {
+        (Some(first), Some(last)) => (first.block_number, last.block_number),
+        _ => return String::from("No gas prices"),
+    };
+    let dimensions = [
+        GasDimension::L1Gas,
+        GasDimension::L1DataGas,
+        GasDimension::L2Gas,
+    ];
+    let mut chart = format!(
+        "Gas prices ({}) from block {first} to block {last}\n",
+        unit.name()
+    );
+    for dimension in dimensions {
+        let values: Vec<U256> = samples
+            .iter()
+            .map(|sample| sample.gas_prices.price(dimension).in_unit(unit))
+            .collect();
+        let min = values.iter().min().copied().unwrap_or_default();
+        let max = values.iter().max().copied().unwrap_or_default();
+        chart.push_str(&format!(
+            "{:>11} {} min: {min} max: {max}\n",
+            dimension.name(),
+            sparkline(&values)
+        ));
+    }
+    chart
+}
+
+/// Renders values as a line of block characters, scaled between their min and max.
+pub fn sparkline(values: &[U256]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = values.iter().min().copied().unwrap_or_default();
+    let max = values.iter().max().copied().unwrap_or_default();
+    let range = (max - min).low_u128() as f64;
+    values
+        .iter()
+        .map(|value| {
+            if range == 0.0 {
+                BARS[0]
+            } else {
+                let ratio = (*value - min).low_u128() as f64 / range;
+                BARS[(ratio * (BARS.len() - 1) as f64).round() as usize]
+            }
+        })
+        .collect()
+}
+
+/// Query the gas prices of every block in a window.
+/// Windows are capped at [`MAX_WINDOW_BLOCKS`] blocks.
+/// # Arguments
+/// * `block_number` - The last block of the window.
+/// * `window` - The window size.
+/// * `network_gateway_url` - The network gateway URL.
+/// # Returns
+/// The gas prices, from the most recent block to the oldest one.
+pub fn query_window_gas_prices(
+    block_number: &str,
+    window: &BlockWindow,
+    network_gateway_url: &str,
+) -> Result<Vec<GasPrices>> {
+    let last_block = query_block(block_number, network_gateway_url)?;
+    let last_block_number = last_block.block_number()?;
+    let mut gas_prices = vec![last_block.gas_prices()?];
+    let oldest_block_number = match window {
+        BlockWindow::Blocks(n) => {
+            last_block_number.saturating_sub((*n).clamp(1, MAX_WINDOW_BLOCKS) - 1)
+        }
+        BlockWindow::Seconds(_) => last_block_number.saturating_sub(MAX_WINDOW_BLOCKS - 1),
+    };
+    let oldest_timestamp = match window {
+        BlockWindow::Blocks(_) => 0,
+        BlockWindow::Seconds(seconds) => last_block.timestamp()?.saturating_sub(*seconds),
+    };
+    for number in (oldest_block_number..last_block_number).rev() {
+        debug!("querying block {} for gas price statistics", number);
+        let block = query_block(&number.to_string(), network_gateway_url)?;
+        if block.timestamp()? < oldest_timestamp {
+            break;
+        }
+        gas_prices.push(block.gas_prices()?);
+    }
+    Ok(gas_prices)
+}
+
+/// Query the gas price statistics over a window of blocks.
+/// # Arguments
+/// * `block_number` - The last block of the window.
+/// * `window` - The window size.
+/// * `network_gateway_url` - The network gateway URL.
+/// # Returns
+/// The gas price statistics.
+pub fn query_gas_price_stats(
+    block_number: &str,
+    window: &BlockWindow,
+    network_gateway_url: &str,
+) -> Result<GasPriceStats> {
+    GasPriceStats::from_gas_prices(&query_window_gas_prices(
+        block_number,
+        window,
+        network_gateway_url,
+    )?)
+}
+
+fn components(gas_prices: &GasPrices) -> [U256; 6] {
+    [
+        gas_prices.l1_gas.price_in_wei,
+        gas_prices.l1_gas.price_in_fri,
+        gas_prices.l1_data_gas.price_in_wei,
+        gas_prices.l1_data_gas.price_in_fri,
+        gas_prices.l2_gas.price_in_wei,
+        gas_prices.l2_gas.price_in_fri,
+    ]
+}
+
+fn from_components(components: [U256; 6]) -> GasPrices {
+    GasPrices {
+        l1_gas: ResourcePrice {
+            price_in_wei: components[0],
+            price_in_fri: components[1],
+        },
+        l1_data_gas: ResourcePrice {
+            price_in_wei: components[2],
+            price_in_fri: components[3],
+        },
+        l2_gas: ResourcePrice {
+            price_in_wei: components[4],
+            price_in_fri: components[5],
+        },
+    }
+}
+
+/// Returns the median of sorted values, the average of the two middle ones for an even count.
+pub(crate) fn median(sorted: &[U256]) -> U256 {
+    let middle = sorted.len() / 2;
+    match sorted.len() % 2 {
+        0 => {
+            sorted[middle - 1] / 2
+                + sorted[middle] / 2
+                + (sorted[middle - 1] % 2 + sorted[middle] % 2) / 2
+        }
+        _ => sorted[middle],
+    }
+}
+
+fn mean(values: &[U256]) -> U256 {
+    let sum = values
+        .iter()
+        .fold(U256::zero(), |sum, value| sum.saturating_add(*value));
+    sum / values.len()
+}
+
+/// Nearest-rank percentile.
+fn percentile(sorted: &[U256], p: usize) -> U256 {
+    let rank = (p * sorted.len()).div_ceil(100);
+    sorted[rank.max(1) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn l1_gas_price(price: u64) -> GasPrices {
+        GasPrices {
+            l1_gas: ResourcePrice {
+                price_in_wei: price.into(),
+                price_in_fri: (price * 1000).into(),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn sorted(values: &[u64]) -> Vec<U256> {
+        let mut values: Vec<U256> = values.iter().map(|&value| value.into()).collect();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn block_window_from_str() {
+        assert_eq!("100".parse(), Ok(BlockWindow::Blocks(100)));
+        assert_eq!("90s".parse(), Ok(BlockWindow::Seconds(90)));
+        assert_eq!("30m".parse(), Ok(BlockWindow::Seconds(1800)));
+        assert_eq!("2h".parse(), Ok(BlockWindow::Seconds(7200)));
+        assert_eq!("1d".parse(), Ok(BlockWindow::Seconds(86400)));
+        assert_eq!(
+            MAX_WINDOW_BLOCKS.to_string().parse(),
+            Ok(BlockWindow::Blocks(MAX_WINDOW_BLOCKS))
+        );
+    }
+
+    #[test]
+    fn invalid_block_windows() {
+        for window in ["", "0", "0m", "m", "-1", "1.5h", "10w", "1000001"] {
+            assert!(window.parse::<BlockWindow>().is_err(), "{window}");
+        }
+        let too_long = format!("{}d", u64::MAX / 1000);
+        assert!(too_long.parse::<BlockWindow>().is_err());
+    }
+
+    #[test]
+    fn median_of_odd_and_even_sizes() {
+        assert_eq!(median(&sorted(&[7])), U256::from(7));
+        assert_eq!(median(&sorted(&[1, 3, 8])), U256::from(3));
+        assert_eq!(median(&sorted(&[1, 2])), U256::from(1));
+        assert_eq!(median(&sorted(&[1, 3])), U256::from(2));
+        assert_eq!(median(&sorted(&[3, 3, 4, 100])), U256::from(3));
+        assert_eq!(median(&[U256::MAX, U256::MAX]), U256::MAX);
+    }
+
+    #[test]
+    fn nearest_rank_percentile() {
+        let values = sorted(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(percentile(&values, 90), U256::from(9));
+        assert_eq!(percentile(&values, 100), U256::from(10));
+        assert_eq!(percentile(&values, 0), U256::from(1));
+        assert_eq!(percentile(&sorted(&[5]), 90), U256::from(5));
+    }
+
+    #[test]
+    fn gas_price_stats() {
+        let gas_prices: Vec<GasPrices> = [40, 10, 30, 20].into_iter().map(l1_gas_price).collect();
+        let stats = GasPriceStats::from_gas_prices(&gas_prices).unwrap();
+        assert_eq!(stats.blocks, 4);
+        assert_eq!(stats.min, l1_gas_price(10));
+        assert_eq!(stats.median, l1_gas_price(25));
+        assert_eq!(stats.mean, l1_gas_price(25));
+        assert_eq!(stats.p90, l1_gas_price(40));
+        assert_eq!(stats.max, l1_gas_price(40));
+        assert_eq!(stats.get(PriceStat::Min), stats.min);
+    }
+
+    #[test]
+    fn gas_price_stats_of_an_empty_window() {
+        assert!(GasPriceStats::from_gas_prices(&[]).is_err());
+    }
+}
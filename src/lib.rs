@@ -46,24 +46,65 @@
 //! --source-block-number 21410 \
 //! --destination-block-number 15925
 //! ```
+//!
+//! ### Gas price
+//!
+//! #### History
+//!
+//! Export the gas prices of a range of blocks, as a chart, CSV or JSON.
+//!
+//! ```bash
+//! $ howmuch gas-price history --network mainnet --from 15000 --to 15900 --step 100 --format csv
+//! ```
+pub mod abi;
+pub mod account;
+pub mod accounting;
+pub mod analyze;
+pub mod cache;
 pub mod cli;
 pub mod currencies;
+pub mod date;
+pub mod estimate;
+pub mod gas_price;
 pub mod model;
+pub mod network;
+pub mod reconcile;
+pub mod replay;
 pub mod resources;
-use ethers::{types::U256, utils};
+pub mod scenario;
+mod setting;
+#[cfg(feature = "test-support")]
+pub mod testing;
+pub mod trace;
+pub mod units;
+use estimate::{Derivation, Estimate, PriceSource};
+use ethers::types::U256;
 use eyre::Result;
-use log::debug;
-use model::{Block, Transaction, TransactionReceipt};
+use gas_price::{BlockWindow, PriceWindow};
+use log::{debug, warn};
+use model::{
+    Block, FeeUnit, GasConsumed, GasPrices, StaticFee, Transaction, TransactionReceipt,
+    TransactionTrace,
+};
+use network::{ExecutionLimits, Network};
+use units::Unit;
 
 /// Simulate cost of a transaction on another network.
+/// When the receipt reports its L1 gas, L1 data gas and L2 gas consumption,
+/// each of them is re-priced with the matching destination gas price.
+/// Otherwise the fee is scaled by the ratio of the block gas prices.
+/// The transaction isn't checked against the execution limits of the destination network,
+/// see [`EstimateOptions::check_limits`] to do so.
 /// # Arguments
 /// * `tx_hash` - The transaction hash.
 /// * `source_network_gateway_url` - The source network gateway URL.
 /// * `destination_network_gateway_url` - The destination network gateway URL.
-/// * `source_block_number` - The source block number.
+/// * `source_block_number` - The source block number, defaults to the block which included the transaction.
 /// * `destination_block_number` - The destination block number.
+/// * `usd` - Whether to display the dollar cost estimation.
 /// # Returns
 /// The estimated fees.
+/// See [`estimate_cost`] and [`EstimateOptions::price_window`] to price the fee over a window of blocks.
 /// # Example
 /// ```rust
 /// use howmuch_rs::{estimate_cost_on_network, model::Transaction};
@@ -93,44 +134,425 @@ pub fn estimate_cost_on_network(
     destination_block_number: &Option<u32>,
     usd: &Option<bool>,
 ) -> Result<String> {
-    let source_block_number = match source_block_number {
-        Some(block_number) => block_number.to_string(),
-        None => "latest".to_string(),
+    let options = EstimateOptions {
+        source_block_number: *source_block_number,
+        destination_block_number: *destination_block_number,
+        usd: *usd,
+        ..Default::default()
     };
-    let destination_block_number = match destination_block_number {
+    estimate_cost(
+        Some(tx_hash),
+        source_network_gateway_url,
+        destination_network_gateway_url,
+        &options,
+    )
+}
+
+/// Options of [`estimate_cost`].
+/// Each of the files and the destination gas prices replaces a network query,
+/// so that an estimate can run fully offline.
+#[derive(Debug, Default, Clone)]
+pub struct EstimateOptions {
+    /// The source block number, defaults to the block which included the transaction.
+    pub source_block_number: Option<u32>,
+    /// The destination block number, defaults to the latest block.
+    pub destination_block_number: Option<u32>,
+    /// Whether to display the dollar cost estimation.
+    pub usd: Option<bool>,
+    /// Price the destination fee with a gas price statistic over a window of blocks.
+    pub price_window: Option<PriceWindow>,
+    /// File containing the source transaction receipt.
+    pub source_receipt_file: Option<String>,
+    /// File containing the source block.
+    pub source_block_file: Option<String>,
+    /// File containing the destination block.
+    pub destination_block_file: Option<String>,
+    /// The destination gas prices.
+    pub destination_gas_prices: Option<GasPrices>,
+    /// The unit fees are displayed in, defaults to the token paying the fee.
+    pub unit: Option<Unit>,
+    /// The execution limits of the destination network,
+    /// defaults to the limits of the destination network when it is a public one.
+    /// Providing them checks the transaction against them.
+    pub execution_limits: Option<ExecutionLimits>,
+    /// Whether to check the transaction against the execution limits of the destination network,
+    /// adding a caveat for each one it exceeds. The check queries the transaction and its declared class.
+    pub check_limits: bool,
+    /// Whether exceeding the destination execution limits is an error, rather than a caveat.
+    /// Limits which are unknown, such as those of a custom destination, are an error too.
+    /// Implies [`EstimateOptions::check_limits`].
+    pub strict_limits: bool,
+}
+
+/// Simulate cost of a transaction on another network, reading inputs from files when provided.
+/// See [`estimate_cost_on_network`].
+/// # Arguments
+/// * `tx_hash` - The transaction hash, not needed when a source receipt file is provided.
+/// * `source_network_gateway_url` - The source network gateway URL.
+/// * `destination_network_gateway_url` - The destination network gateway URL.
+/// * `options` - The estimate options.
+/// # Returns
+/// The estimated fees.
+/// # Example
+/// ```rust
+/// use howmuch_rs::{estimate_cost, model::GasPrices, model::ResourcePrice, EstimateOptions};
+/// use ethers::types::U256;
+/// let options = EstimateOptions {
+///     source_receipt_file: Some("tests/example.json".to_string()),
+///     source_block_file: Some("tests/example_block.json".to_string()),
+///     destination_gas_prices: Some(GasPrices {
+///         l1_gas: ResourcePrice { price_in_wei: U256::from(10_000_000_000u64), price_in_fri: U256::zero() },
+///         ..Default::default()
+///     }),
+///     ..Default::default()
+/// };
+/// let fees = estimate_cost(None, "", "", &options).unwrap();
+/// assert_eq!(fees, "0.000339950000000000 ETH");
+/// ```
+pub fn estimate_cost(
+    tx_hash: Option<&str>,
+    source_network_gateway_url: &str,
+    destination_network_gateway_url: &str,
+    options: &EstimateOptions,
+) -> Result<String> {
+    let estimate = estimate(
+        tx_hash,
+        source_network_gateway_url,
+        destination_network_gateway_url,
+        options,
+    )?;
+    // The caveats are only kept by the estimate, so they are logged here rather than lost.
+    for caveat in &estimate.caveats {
+        warn!("{}", caveat);
+    }
+    estimate.try_render()
+}
+
+/// Estimate the fee of a transaction on another network, keeping every intermediate value.
+/// See [`estimate_cost`].
+/// # Returns
+/// The estimate, which can be displayed or explained.
+/// # Example
+/// ```rust
+/// use howmuch_rs::{estimate, model::GasPrices, model::ResourcePrice, EstimateOptions};
+/// use ethers::types::U256;
+/// let options = EstimateOptions {
+///     source_receipt_file: Some("tests/example.json".to_string()),
+///     source_block_file: Some("tests/example_block.json".to_string()),
+///     destination_gas_prices: Some(GasPrices {
+///         l1_gas: ResourcePrice { price_in_wei: U256::from(10_000_000_000u64), price_in_fri: U256::zero() },
+///         ..Default::default()
+///     }),
+///     ..Default::default()
+/// };
+/// let estimate = estimate(None, "", "", &options).unwrap();
+/// println!("{}", estimate.explain().unwrap());
+/// ```
+pub fn estimate(
+    tx_hash: Option<&str>,
+    source_network_gateway_url: &str,
+    destination_network_gateway_url: &str,
+    options: &EstimateOptions,
+) -> Result<Estimate> {
+    let mut caveats = vec![];
+    let destination_block_number = match options.destination_block_number {
         Some(block_number) => block_number.to_string(),
         None => "latest".to_string(),
     };
-    debug!("querying transaction {} on source network", tx_hash);
-    let source_tx = query_tx_receipt(tx_hash, source_network_gateway_url)?;
+    let source_tx = match (&options.source_receipt_file, tx_hash) {
+        (Some(filename), _) => TransactionReceipt::try_from_file(filename)?,
+        (None, Some(tx_hash)) => {
+            debug!("querying transaction {} on source network", tx_hash);
+            query_tx_receipt(tx_hash, source_network_gateway_url)?
+        }
+        (None, None) => {
+            return Err(eyre::eyre!(
+                "Provide either a transaction hash or a source receipt file"
+            ))
+        }
+    };
+    let check_limits =
+        options.check_limits || options.strict_limits || options.execution_limits.is_some();
+    let execution_limits = options.execution_limits.or_else(|| {
+        Network::from_gateway_url(destination_network_gateway_url)
+            .map(|network| network.execution_limits())
+    });
+    if !check_limits {
+        debug!("skipping the execution limits check");
+    } else if let Some(execution_limits) = execution_limits {
+        // The transaction itself is only queried when the estimate isn't run offline.
+        let transaction = match (&options.source_receipt_file, tx_hash) {
+            (None, Some(tx_hash)) => Some(query_tx(tx_hash, source_network_gateway_url)?),
+            (_, _) => None,
+        };
+        let violations = check_execution_limits(
+            &execution_limits,
+            &source_tx,
+            transaction.as_ref(),
+            source_network_gateway_url,
+        )?;
+        if !violations.is_empty() && options.strict_limits {
+            return Err(eyre::eyre!(
+                "transaction exceeds the limits of the destination network: it {}",
+                violations.join(", ")
+            ));
+        }
+        for violation in violations {
+            caveats.push(format!(
+                "transaction {violation} on the destination network"
+            ));
+        }
+    } else if options.strict_limits {
+        return Err(eyre::eyre!(
+            "the execution limits of the destination network are unknown, \
+             provide them to check the transaction against them"
+        ));
+    }
     let actual_fee = source_tx.actual_fee()?;
+    let fee_unit = source_tx.fee_unit()?;
     debug!("transaction actual fee: {}", actual_fee);
-    debug!("querying block {} on source network", source_block_number);
-    let source_block = query_block(&source_block_number, source_network_gateway_url)?;
-    let gas_price = source_block.gas_price()?;
-    debug!("source block gas price: {}", gas_price);
-    let tx_static_fee = compute_static_tx_fee(actual_fee, gas_price)?;
-    debug!("transaction static fee: {}", tx_static_fee);
-    debug!(
-        "querying block {} on destination network",
-        destination_block_number
-    );
-    let destination_block =
-        query_block(&destination_block_number, destination_network_gateway_url)?;
-    let destination_gas_price = destination_block.gas_price()?;
-    debug!("destination block gas price: {}", destination_gas_price);
-    let destination_tx_actual_fee = compute_actual_tx_fee(tx_static_fee, destination_gas_price)?;
+    let mut source_version = None;
+    let derivation = match source_tx.gas_consumed()? {
+        Some(gas_consumed) => {
+            debug!("transaction gas consumed: {:?}", gas_consumed);
+            Derivation::Gas(gas_consumed)
+        }
+        None => {
+            let source_block = match &options.source_block_file {
+                Some(filename) => {
+                    let source_block = Block::try_from_file(filename)?;
+                    resolve_source_block_number(
+                        &Some(source_block.block_number()?),
+                        source_tx.block_number()?,
+                        &mut caveats,
+                    );
+                    source_block
+                }
+                None => {
+                    let source_block_number = resolve_source_block_number(
+                        &options.source_block_number.map(u64::from),
+                        source_tx.block_number()?,
+                        &mut caveats,
+                    );
+                    debug!("querying block {} on source network", source_block_number);
+                    query_block(&source_block_number, source_network_gateway_url)?
+                }
+            };
+            source_version = source_block.starknet_version()?;
+            let gas_price = source_block.gas_price()?;
+            debug!("source block gas price: {}", gas_price);
+            let tx_static_fee = compute_static_tx_fee(actual_fee, gas_price)?;
+            debug!("transaction static fee: {}", tx_static_fee);
+            if !tx_static_fee.remainder().is_zero() {
+                let caveat = format!(
+                    "actual fee {} {} is not a multiple of the source gas price {} {}/gas, \
+                     the source block is likely not the one the fee was charged at",
+                    actual_fee,
+                    fee_unit.name(),
+                    gas_price,
+                    fee_unit.name()
+                );
+                caveats.push(caveat);
+            }
+            Derivation::StaticFee {
+                static_fee: tx_static_fee,
+                source_block_number: source_block.block_number().ok(),
+            }
+        }
+    };
+    let mut destination_version = None;
+    let (destination_gas_prices, price_source, fee_range) = match (
+        &options.destination_gas_prices,
+        &options.destination_block_file,
+        &options.price_window,
+    ) {
+        (Some(gas_prices), _, _) => (*gas_prices, PriceSource::Literal, None),
+        (None, Some(filename), _) => {
+            let destination_block = Block::try_from_file(filename)?;
+            destination_version = destination_block.starknet_version()?;
+            (
+                destination_block.gas_prices()?,
+                PriceSource::BlockFile(filename.clone()),
+                None,
+            )
+        }
+        (None, None, None) => {
+            debug!(
+                "querying block {} on destination network",
+                destination_block_number
+            );
+            let destination_block =
+                query_block(&destination_block_number, destination_network_gateway_url)?;
+            destination_version = destination_block.starknet_version()?;
+            (
+                destination_block.gas_prices()?,
+                PriceSource::Block {
+                    block_number: destination_block.block_number().ok(),
+                    network_gateway_url: destination_network_gateway_url.to_string(),
+                },
+                None,
+            )
+        }
+        (None, None, Some(price_window)) => {
+            debug!(
+                "querying {:?} ending at block {} on destination network",
+                price_window.window, destination_block_number
+            );
+            let stats = gas_price::query_gas_price_stats(
+                &destination_block_number,
+                &price_window.window,
+                destination_network_gateway_url,
+            )?;
+            debug!("destination gas price statistics: {:?}", stats);
+            if let BlockWindow::Seconds(seconds) = price_window.window {
+                if stats.blocks as u64 >= gas_price::MAX_WINDOW_BLOCKS {
+                    caveats.push(format!(
+                        "the {seconds}s window is capped at the last {} blocks",
+                        gas_price::MAX_WINDOW_BLOCKS
+                    ));
+                }
+            }
+            let fee_range = (
+                derivation.fee_at(&stats.min, fee_unit)?,
+                derivation.fee_at(&stats.max, fee_unit)?,
+            );
+            (
+                stats.get(price_window.stat),
+                PriceSource::Window {
+                    stat: price_window.stat,
+                    blocks: stats.blocks,
+                    network_gateway_url: destination_network_gateway_url.to_string(),
+                },
+                Some(fee_range),
+            )
+        }
+    };
+    debug!("destination gas prices: {:?}", destination_gas_prices);
+    let destination_tx_actual_fee = derivation.fee_at(&destination_gas_prices, fee_unit)?;
     debug!(
         "transaction actual fee on destination network: {}",
         destination_tx_actual_fee
     );
-    let output = utils::format_units(destination_tx_actual_fee, "ether")?;
-    match usd {
-        None | Some(false) => Ok(format!("{output} ETH")),
-        Some(true) => Ok(format!(
-            "{output} ETH ({})",
-            &crate::currencies::format_dollar_cost(destination_tx_actual_fee)
-        )),
+    if let (Some(source_version), Some(destination_version)) =
+        (&source_version, &destination_version)
+    {
+        if source_version != destination_version {
+            caveats.push(format!(
+                "source block was produced by StarkNet {source_version} \
+                 but destination block by StarkNet {destination_version}"
+            ));
+        }
+    }
+    match &derivation {
+        Derivation::StaticFee { static_fee, .. } => {
+            let destination_gas_price = destination_gas_prices.l1_gas.in_unit(fee_unit);
+            let exact = match static_fee.actual_fee.checked_mul(destination_gas_price) {
+                Some(scaled_fee) => {
+                    static_fee.gas_price.is_zero() || (scaled_fee % static_fee.gas_price).is_zero()
+                }
+                None => true,
+            };
+            if !exact {
+                caveats.push(format!(
+                    "destination fee is rounded down to the nearest {}",
+                    fee_unit.name()
+                ));
+            }
+        }
+        Derivation::Gas(gas_consumed) => {
+            let unpriced = [
+                (
+                    "L1 data gas",
+                    gas_consumed.l1_data_gas,
+                    destination_gas_prices.l1_data_gas,
+                ),
+                ("L2 gas", gas_consumed.l2_gas, destination_gas_prices.l2_gas),
+            ];
+            for (name, consumed, price) in unpriced {
+                if !consumed.is_zero() && price.in_unit(fee_unit).is_zero() {
+                    caveats.push(format!(
+                        "destination publishes no {name} price, the {consumed} {name} consumed are free"
+                    ));
+                }
+            }
+        }
+    }
+    let usd = options.usd.unwrap_or(false);
+    let display_unit = options.unit.unwrap_or(Unit::token(fee_unit));
+    Ok(Estimate {
+        fee_unit,
+        source_fee: actual_fee,
+        derivation,
+        destination_gas_prices,
+        price_source,
+        destination_fee: destination_tx_actual_fee,
+        fee_range,
+        display_unit,
+        usd,
+        token_price: if usd || display_unit == Unit::Usd {
+            currencies::get_token_price(fee_unit)
+        } else {
+            None
+        },
+
+        caveats,
+    })
+}
+
+/// Check a transaction against the execution limits of a network.
+/// The calldata and declared class are only checked when the transaction is provided.
+/// # Returns
+/// How the transaction exceeds the limits, if it does.
+fn check_execution_limits(
+    execution_limits: &ExecutionLimits,
+    tx_receipt: &TransactionReceipt,
+    transaction: Option<&Transaction>,
+    network_gateway_url: &str,
+) -> Result<Vec<String>> {
+    let steps = tx_receipt.resources_used()?.steps as u64;
+    let (calldata_length, class_size) = match transaction {
+        Some(transaction) => {
+            let class_size = match transaction.declared_class_hash()? {
+                Some(class_hash) => {
+                    debug!("querying class {} on source network", class_hash);
+                    Some(query_class_by_hash(&class_hash, network_gateway_url)?.len())
+                }
+                None => None,
+            };
+            (transaction.calldata_length()?, class_size)
+        }
+        None => (None, None),
+    };
+    Ok(execution_limits.violations(Some(steps), calldata_length, class_size))
+}
+
+/// Pick the block whose gas price the transaction fee was charged at.
+/// The fee was charged at the gas price of the block which included the
+/// transaction, so overriding it with another block is only worth a caveat.
+fn resolve_source_block_number(
+    source_block_number: &Option<u64>,
+    inclusion_block_number: Option<u64>,
+    caveats: &mut Vec<String>,
+) -> String {
+    match (source_block_number, inclusion_block_number) {
+        (Some(block_number), Some(inclusion_block_number)) => {
+            if *block_number != inclusion_block_number {
+                let caveat = format!(
+                    "transaction was included in block {} but block {} is used as the source, \
+                     the estimate is likely wrong",
+                    inclusion_block_number, block_number
+                );
+                caveats.push(caveat);
+            }
+            block_number.to_string()
+        }
+        (Some(block_number), None) => block_number.to_string(),
+        (None, Some(inclusion_block_number)) => inclusion_block_number.to_string(),
+        (None, None) => {
+            let caveat = "transaction is not included in a block yet, using the pending block";
+            caveats.push(caveat.to_string());
+            "pending".to_string()
+        }
     }
 }
 
@@ -174,6 +596,51 @@ pub fn query_tx_receipt(tx_hash: &str, network_gateway_url: &str) -> Result<Tran
     ))?))
 }
 
+/// Query a transaction trace from a network.
+/// # Arguments
+/// * `tx_hash` - The transaction hash.
+/// * `network_gateway_url` - The network gateway URL.
+/// # Returns
+/// The transaction trace, with its validate, execute and fee transfer invocations.
+/// # Errors
+/// If the transaction is not found, an error is returned.
+pub fn query_tx_trace(tx_hash: &str, network_gateway_url: &str) -> Result<TransactionTrace> {
+    Ok(TransactionTrace(http_get(&format!(
+        "{}/get_transaction_trace?transactionHash={}",
+        network_gateway_url, tx_hash
+    ))?))
+}
+
+/// Query the class hash of a contract from a network.
+/// # Arguments
+/// * `contract_address` - The contract address.
+/// * `network_gateway_url` - The network gateway URL.
+/// # Returns
+/// The class hash, as a `0x` prefixed hexadecimal string.
+pub fn query_class_hash_at(contract_address: &str, network_gateway_url: &str) -> Result<String> {
+    let body = http_get(&format!(
+        "{}/get_class_hash_at?contractAddress={}",
+        network_gateway_url, contract_address
+    ))?;
+    json::parse(&body)?
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| eyre::eyre!("Invalid class hash for contract {}", contract_address))
+}
+
+/// Query a contract class from a network.
+/// # Arguments
+/// * `class_hash` - The class hash.
+/// * `network_gateway_url` - The network gateway URL.
+/// # Returns
+/// The contract class, along with its ABI.
+pub fn query_class_by_hash(class_hash: &str, network_gateway_url: &str) -> Result<String> {
+    http_get(&format!(
+        "{}/get_class_by_hash?classHash={}",
+        network_gateway_url, class_hash
+    ))
+}
+
 /// Query a block from a network.
 /// # Arguments
 /// * `block_number` - The block number.
@@ -192,43 +659,132 @@ pub fn query_block(block_number: &str, network_gateway_url: &str) -> Result<Bloc
     ))?))
 }
 
-/// Compute the static part of the transaction fee.
+/// Compute the static part of the transaction fee, i.e. the gas units it paid for.
+/// The gas units are kept as the `actual_fee / block_gas_price` ratio so that
+/// re-pricing them does not lose the division remainder.
 /// # Arguments
 /// * `actual_fee` - The actual fee of the transaction.
 /// * `block_gas_price` - The block gas price.
 /// # Returns
 /// The static part of the transaction fee.
+/// # Errors
+/// If the fee is not zero but the block gas price is, no gas units can be derived.
 /// # Example
 /// ```
 /// use howmuch_rs::compute_static_tx_fee;
 /// use ethers::types::U256;
-/// let static_fee = compute_static_tx_fee(U256::from(100), U256::from(10)).unwrap();
+/// let static_fee = compute_static_tx_fee(U256::from(105), U256::from(10)).unwrap();
+/// assert_eq!(static_fee.gas_units(), U256::from(10));
+/// assert_eq!(static_fee.remainder(), U256::from(5));
 /// ```
-pub fn compute_static_tx_fee(actual_fee: U256, block_gas_price: U256) -> Result<U256> {
-    if block_gas_price > actual_fee {
-        return Err(eyre::eyre!("Block gas price must be lower than actual fee"));
+pub fn compute_static_tx_fee(actual_fee: U256, block_gas_price: U256) -> Result<StaticFee> {
+    if block_gas_price.is_zero() && !actual_fee.is_zero() {
+        return Err(eyre::eyre!(
+            "Cannot derive gas units from a non-zero fee and a zero block gas price"
+        ));
     }
-    Ok(actual_fee / block_gas_price)
+    Ok(StaticFee {
+        actual_fee,
+        gas_price: block_gas_price,
+    })
 }
 
-/// Compute the actuall fee of a transaction.
+/// Compute the actual fee of a transaction.
+/// The result is rounded down to the nearest unit.
 /// # Arguments
 /// * `tx_static_fee` - The static part of the transaction fee.
 /// * `block_gas_price` - The block gas price.
 /// # Returns
 /// The actual fee of the transaction.
+/// # Errors
+/// If the fee does not fit in 256 bits, or if the fee is not zero but its gas price is.
+/// # Example
+/// ```
+/// use howmuch_rs::{compute_actual_tx_fee, compute_static_tx_fee};
+/// use ethers::types::U256;
+/// let static_fee = compute_static_tx_fee(U256::from(105), U256::from(10)).unwrap();
+/// let actual_fee = compute_actual_tx_fee(static_fee, U256::from(20)).unwrap();
+/// assert_eq!(actual_fee, U256::from(210));
+/// ```
+pub fn compute_actual_tx_fee(tx_static_fee: StaticFee, block_gas_price: U256) -> Result<U256> {
+    if tx_static_fee.actual_fee.is_zero() {
+        return Ok(U256::zero());
+    }
+    if tx_static_fee.gas_price.is_zero() {
+        return Err(eyre::eyre!(
+            "Cannot scale a non-zero fee charged at a zero gas price"
+        ));
+    }
+    let scaled_fee = tx_static_fee
+        .actual_fee
+        .checked_mul(block_gas_price)
+        .ok_or_else(|| eyre::eyre!("Transaction fee overflows"))?;
+    Ok(scaled_fee / tx_static_fee.gas_price)
+}
+
+/// Compute the fee of a transaction from its gas consumption.
+/// Each fee market dimension is priced with its own gas price.
+/// # Arguments
+/// * `gas_consumed` - The gas consumed by the transaction.
+/// * `gas_prices` - The block gas prices.
+/// * `unit` - The unit the fee is paid in.
+/// # Returns
+/// The fee of the transaction.
 /// # Example
 /// ```
-/// use howmuch_rs::compute_actual_tx_fee;
+/// use howmuch_rs::compute_tx_fee;
+/// use howmuch_rs::model::{FeeUnit, GasConsumed, GasPrices, ResourcePrice};
 /// use ethers::types::U256;
-/// let actual_fee = compute_actual_tx_fee(U256::from(10), U256::from(10)).unwrap();
+/// let gas_consumed = GasConsumed {
+///     l1_gas: U256::from(10),
+///     l1_data_gas: U256::from(128),
+///     l2_gas: U256::zero(),
+/// };
+/// let gas_prices = GasPrices {
+///     l1_gas: ResourcePrice { price_in_wei: U256::from(100), price_in_fri: U256::from(1000) },
+///     l1_data_gas: ResourcePrice { price_in_wei: U256::from(1), price_in_fri: U256::from(10) },
+///     l2_gas: ResourcePrice::default(),
+/// };
+/// let fee = compute_tx_fee(&gas_consumed, &gas_prices, FeeUnit::Wei).unwrap();
+/// assert_eq!(fee, U256::from(1128));
 /// ```
-pub fn compute_actual_tx_fee(tx_static_fee: U256, block_gas_price: U256) -> Result<U256> {
-    Ok(tx_static_fee * block_gas_price)
+pub fn compute_tx_fee(
+    gas_consumed: &GasConsumed,
+    gas_prices: &GasPrices,
+    unit: FeeUnit,
+) -> Result<U256> {
+    [
+        (gas_consumed.l1_gas, gas_prices.l1_gas),
+        (gas_consumed.l1_data_gas, gas_prices.l1_data_gas),
+        (gas_consumed.l2_gas, gas_prices.l2_gas),
+    ]
+    .iter()
+    .try_fold(U256::zero(), |fee, (gas, price)| {
+        gas.checked_mul(price.in_unit(unit))
+            .and_then(|dimension_fee| fee.checked_add(dimension_fee))
+            .ok_or_else(|| eyre::eyre!("Transaction fee overflows"))
+    })
 }
 
 /// Raw http GET request.
+/// Responses go through the on-disk cache, see [`cache::set_cache_mode`],
+/// and can be recorded or replayed, see [`replay::set_replay_mode`].
 pub fn http_get(url: &str) -> Result<String> {
-    let response = reqwest::blocking::get(url)?;
-    response.text().map_err(|e| e.into())
+    if let Some(body) = replay::replay(url) {
+        return body;
+    }
+    let body = match cache::get(url) {
+        Some(body) => body,
+        None => {
+            let response = reqwest::blocking::get(url)?;
+            let success = response.status().is_success();
+            let body = response.text()?;
+            if success {
+                cache::put(url, &body);
+            }
+            body
+        }
+    };
+    replay::record(url, &body)?;
+    Ok(body)
 }
@@ -1,15 +1,58 @@
+use std::time::Duration;
+
 use clap::Parser;
 use eyre::Result;
 use howmuch_rs::{
-    cli::{Cli, Commands, FeesSubCommands},
-    estimate_cost_on_network,
-    resources::{get_resources_used, Weights},
+    abi::{format_event, SelectorNames},
+    account::{account_overheads, overheads_to_table},
+    accounting::{
+        fee_history_to_csv, fee_history_to_json, fee_history_to_table, find_block_range,
+        query_account_fee_history, ReportFormat,
+    },
+    analyze::{
+        block_capacity, block_usages_to_csv, block_usages_to_json, block_usages_to_table,
+        contract_costs_to_csv, contract_costs_to_json, contract_costs_to_table, find_outliers,
+        query_block_usages, query_contract_costs, query_transaction_costs,
+        transaction_costs_to_table, BLOCK_CAPACITY_NOTE,
+    },
+    cache::{set_cache_mode, CacheMode},
+    cli::{
+        AccountSubCommands, AnalyzeSubCommands, Cli, Commands, FeesSubCommands, GasPriceSubCommands,
+    },
+    currencies::set_price_oracle_url,
+    date::parse_date,
+    estimate,
+    gas_price::{
+        history_to_chart, history_to_csv, history_to_json, query_gas_price_history,
+        watch_gas_price, HistoryFormat, PriceStat, PriceWindow, Threshold, WatchHook,
+    },
+    network::Network,
+    reconcile::get_fee_reconciliation,
+    replay::{set_replay_mode, ReplayMode},
+    resources::{get_fee_summary, get_transaction_receipt},
+    scenario::{compare_scenarios, Scenario, DEFAULT_SCENARIO_NAME},
+    trace::{get_call_tree, FeeAttribution, TraceFormat},
+    units::{convert, parse_amount, Unit},
+    EstimateOptions,
 };
 
 fn main() -> Result<()> {
     env_logger::init();
     // Parse the CLI arguments.
     let cli = Cli::parse();
+    // Configure the response cache.
+    set_cache_mode(match (cli.no_cache, cli.refresh) {
+        (true, _) => CacheMode::Disabled,
+        (false, true) => CacheMode::Refresh,
+        (false, false) => CacheMode::Enabled,
+    });
+    // Configure the response recording or replay.
+    set_replay_mode(match (&cli.record, &cli.replay) {
+        (Some(dir), _) => ReplayMode::Record(dir.into()),
+        (None, Some(dir)) => ReplayMode::Replay(dir.into()),
+        (None, None) => ReplayMode::Off,
+    });
+    set_price_oracle_url(&cli.price_oracle_url);
     // Dispatch the CLI command.
     match &cli.command {
         Commands::Fees(fees_commands) => match &fees_commands.command {
@@ -20,53 +63,427 @@ fn main() -> Result<()> {
                 source_block_number,
                 destination_block_number,
                 usd,
+                price_stat,
+                window,
+                source_receipt_file,
+                source_block_file,
+                destination_block_file,
+                destination_gas_price,
+                explain,
+                unit,
+                max_steps,
+                max_calldata_length,
+                max_class_size,
+                check_limits,
+                strict_limits,
             } => {
-                let actual_fees_on_destination_network = estimate_cost_on_network(
-                    tx_hash,
+                let price_window = window.map(|window| PriceWindow {
+                    stat: price_stat.unwrap_or(PriceStat::Median),
+                    window,
+                });
+                let execution_limits = match (max_steps, max_calldata_length, max_class_size) {
+                    (None, None, None) => None,
+                    (_, _, _) => Some(
+                        Network::from_gateway_url(destination_network_gateway_url)
+                            .unwrap_or(Network::Mainnet)
+                            .execution_limits()
+                            .with_overrides(*max_steps, *max_calldata_length, *max_class_size),
+                    ),
+                };
+                let options = EstimateOptions {
+                    source_block_number: *source_block_number,
+                    destination_block_number: *destination_block_number,
+                    usd: *usd,
+                    price_window,
+                    source_receipt_file: source_receipt_file.clone(),
+                    source_block_file: source_block_file.clone(),
+                    destination_block_file: destination_block_file.clone(),
+                    destination_gas_prices: *destination_gas_price,
+                    unit: *unit,
+                    execution_limits,
+                    check_limits: *check_limits,
+                    strict_limits: *strict_limits,
+                };
+                let actual_fees_on_destination_network = estimate(
+                    tx_hash.as_deref(),
                     source_network_gateway_url,
                     destination_network_gateway_url,
-                    source_block_number,
-                    destination_block_number,
-                    usd,
+                    &options,
                 )?;
-                println!("{}", actual_fees_on_destination_network);
+                if *explain {
+                    print!("{}", actual_fees_on_destination_network.explain()?);
+                } else {
+                    for caveat in &actual_fees_on_destination_network.caveats {
+                        eprintln!("caveat: {}", caveat);
+                    }
+                }
+                println!("{}", actual_fees_on_destination_network.try_render()?);
+            }
+            FeesSubCommands::Trace {
+                tx_hash,
+                gateway_url,
+                trace_file,
+                transaction_file,
+                unit,
+                abi_files,
+                fetch_abis,
+                format,
+                flame_weight,
+                output,
+                weights,
+            } => {
+                let weights = weights.to_weights();
+                let mut call_tree =
+                    get_call_tree(tx_hash.as_deref(), gateway_url, trace_file.as_deref())?;
+                let mut names = SelectorNames::try_from_files(abi_files)?;
+                if *fetch_abis {
+                    names.extend_from_contracts(&call_tree.contracts(), gateway_url);
+                }
+                call_tree.name_calls(&names);
+                let trace = match format {
+                    TraceFormat::Folded => call_tree.to_folded(&weights, *flame_weight),
+                    TraceFormat::Tree => {
+                        let tx_receipt = match (tx_hash, transaction_file) {
+                            (None, None) => None,
+                            (_, _) => Some(get_transaction_receipt(
+                                tx_hash.as_deref(),
+                                Some(gateway_url),
+                                transaction_file.as_deref(),
+                            )?),
+                        };
+                        let fee = match tx_receipt {
+                            Some(tx_receipt) => {
+                                Some(FeeAttribution::from_receipt(&tx_receipt, *unit)?)
+                            }
+                            None => None,
+                        };
+                        call_tree.to_tree(&weights, fee.as_ref())?
+                    }
+                };
+                match output {
+                    Some(filename) => std::fs::write(filename, trace)?,
+                    None => println!("{}", trace),
+                }
+            }
+            FeesSubCommands::Overhead {
+                tx_hashes,
+                gateway_url,
+                trace_files,
+                account_labels,
+                weights,
+            } => {
+                let mut call_trees = vec![];
+                for tx_hash in tx_hashes {
+                    call_trees.push(get_call_tree(Some(tx_hash), gateway_url, None)?);
+                }
+                for trace_file in trace_files {
+                    call_trees.push(get_call_tree(None, gateway_url, Some(trace_file))?);
+                }
+                let overheads =
+                    account_overheads(&call_trees, account_labels, &weights.to_weights());
+                println!("{}", overheads_to_table(&overheads));
+            }
+            FeesSubCommands::Reconcile {
+                tx_hash,
+                gateway_url,
+                transaction_file,
+                block_file,
+            } => {
+                let reconciliation = get_fee_reconciliation(
+                    tx_hash.as_deref(),
+                    gateway_url,
+                    transaction_file.as_deref(),
+                    block_file.as_deref(),
+                )?;
+                print!("{}", reconciliation);
+                if !reconciliation.is_reconciled() {
+                    return Err(eyre::eyre!("The actual fee does not reconcile"));
+                }
             }
             FeesSubCommands::Summary {
                 tx_hash,
                 gateway_url,
                 transaction_file,
-                steps_weight,
-                pedersen_weight,
-                range_check_weight,
-                ecdsa_weight,
-                bitwise_weight,
-                ec_op_weight,
+                block_file,
+                unit,
+                usd,
+                weights,
                 steps,
                 pedersen,
                 range_check,
                 ecdsa,
                 bitwise,
                 ec_op,
+                scale,
+                scenario,
+                events,
+                abi_files,
+                fetch_abis,
             } => {
-                let weights = Weights::new(
-                    "weight",
-                    *steps_weight,
-                    *pedersen_weight,
-                    *range_check_weight,
-                    *ecdsa_weight,
-                    *bitwise_weight,
-                    *ec_op_weight,
-                );
+                let weights = weights.to_weights();
 
-                let mut resources_used = get_resources_used(
+                let tx_receipt = get_transaction_receipt(
                     tx_hash.as_ref().map(|x| x.as_ref()),
                     gateway_url.as_ref().map(|x| x.as_ref()),
                     transaction_file.as_ref().map(|x| x.as_ref()),
                 )?;
-                resources_used.update(steps, pedersen, range_check, ecdsa, bitwise, ec_op);
+                let mut resources_used = tx_receipt.resources_used()?;
+                resources_used.update(steps, pedersen, range_check, ecdsa, bitwise, ec_op)?;
 
-                let table = resources_used.to_table(&weights);
+                // A receipt read from a file is summarized offline, unless fiat is asked for.
+                let offline = transaction_file.is_some();
+                let fee_summary = get_fee_summary(
+                    &tx_receipt,
+                    gateway_url.as_deref().filter(|_| !offline),
+                    block_file.as_deref(),
+                    *unit,
+                    !offline || *usd || *unit == Some(Unit::Usd),
+                )?;
+                let table = resources_used.to_table_with_fee(&weights, &fee_summary)?;
                 println!("{}", table);
+
+                let mut scenarios = scenario.clone();
+                if !scale.is_empty() {
+                    scenarios.insert(0, Scenario::new(DEFAULT_SCENARIO_NAME, scale.clone()));
+                }
+                if !scenarios.is_empty() {
+                    let comparison = compare_scenarios(
+                        &resources_used,
+                        &weights,
+                        &scenarios,
+                        Some(&fee_summary),
+                    )?;
+                    println!("{}", comparison);
+                }
+
+                if *events {
+                    let events = tx_receipt.events()?;
+                    let mut names = SelectorNames::try_from_files(abi_files)?;
+                    if let (true, Some(gateway_url)) = (*fetch_abis, gateway_url) {
+                        let mut emitters: Vec<(&str, Option<&str>)> = vec![];
+                        for event in &events {
+                            if !emitters.iter().any(|(a, _)| *a == event.from_address) {
+                                emitters.push((&event.from_address, None));
+                            }
+                        }
+                        names.extend_from_contracts(&emitters, gateway_url);
+                    }
+                    println!("Events:");
+                    for event in &events {
+                        println!("  - {}", format_event(event, &names));
+                    }
+                }
+            }
+        },
+        Commands::Account(account_commands) => match &account_commands.command {
+            AccountSubCommands::History {
+                account,
+                network,
+                gateway_url,
+                from,
+                to,
+                from_date,
+                to_date,
+                no_prices,
+                unit,
+                format,
+                output,
+            } => {
+                let gateway_url = gateway_url.as_deref().unwrap_or(network.gateway_url());
+                let (from, to) = match (from, to, from_date, to_date) {
+                    (Some(from), Some(to), _, _) => (*from, *to),
+                    (_, _, Some(from_date), Some(to_date)) => {
+                        find_block_range(parse_date(from_date)?, parse_date(to_date)?, gateway_url)?
+                    }
+                    (_, _, _, _) => {
+                        return Err(eyre::eyre!(
+                            "Provide either a range of blocks or a range of dates"
+                        ))
+                    }
+                };
+                let history =
+                    query_account_fee_history(account, from, to, gateway_url, !*no_prices)?;
+                let history = match format {
+                    ReportFormat::Table => fee_history_to_table(&history, *unit)?,
+                    ReportFormat::Csv => fee_history_to_csv(&history, *unit)?,
+                    ReportFormat::Json => fee_history_to_json(&history, *unit)?,
+                };
+                match output {
+                    Some(filename) => std::fs::write(filename, history)?,
+                    None => println!("{}", history),
+                }
+            }
+        },
+        Commands::Analyze(analyze_commands) => match &analyze_commands.command {
+            AnalyzeSubCommands::Contracts {
+                network,
+                gateway_url,
+                from,
+                to,
+                top,
+                format,
+                output,
+                weights,
+            } => {
+                let gateway_url = gateway_url.as_deref().unwrap_or(network.gateway_url());
+                let weights = weights.to_weights();
+                let (mut costs, caveats) = query_contract_costs(*from, *to, gateway_url, &weights)?;
+                for caveat in caveats {
+                    eprintln!("caveat: {}", caveat);
+                }
+                if let Some(top) = top {
+                    costs.truncate(*top);
+                }
+                let costs = match format {
+                    ReportFormat::Table => contract_costs_to_table(&costs, &weights)?,
+                    ReportFormat::Csv => contract_costs_to_csv(&costs, &weights)?,
+                    ReportFormat::Json => contract_costs_to_json(&costs, &weights)?,
+                };
+                match output {
+                    Some(filename) => std::fs::write(filename, costs)?,
+                    None => println!("{}", costs),
+                }
+            }
+            AnalyzeSubCommands::Transactions {
+                network,
+                gateway_url,
+                from,
+                to,
+                top,
+                outlier_factor,
+                min_samples,
+                abi_files,
+                output,
+            } => {
+                let gateway_url = gateway_url.as_deref().unwrap_or(network.gateway_url());
+                let names = SelectorNames::try_from_files(abi_files)?;
+                let costs = query_transaction_costs(*from, *to, gateway_url)?;
+                let outliers = find_outliers(&costs, *outlier_factor, *min_samples)?;
+                let report =
+                    transaction_costs_to_table(&costs, *top, &outliers, *outlier_factor, &names)?;
+                match output {
+                    Some(filename) => std::fs::write(filename, report)?,
+                    None => println!("{}", report),
+                }
+            }
+            AnalyzeSubCommands::Blocks {
+                network,
+                gateway_url,
+                from,
+                to,
+                block_max_steps,
+                format,
+                output,
+                weights,
+            } => {
+                let gateway_url = gateway_url.as_deref().unwrap_or(network.gateway_url());
+                let capacity = block_capacity(*block_max_steps, &weights.to_weights());
+                let usages = query_block_usages(*from, *to, gateway_url)?;
+                let report = match format {
+                    ReportFormat::Table => block_usages_to_table(&usages, &capacity)?,
+                    ReportFormat::Csv => {
+                        eprintln!("note: {}", BLOCK_CAPACITY_NOTE);
+                        block_usages_to_csv(&usages, &capacity)?
+                    }
+                    ReportFormat::Json => block_usages_to_json(&usages, &capacity)?,
+                };
+                match output {
+                    Some(filename) => std::fs::write(filename, report)?,
+                    None => println!("{}", report),
+                }
+            }
+        },
+        Commands::Convert { amount, from, to } => {
+            let fee_unit = from
+                .fee_unit()
+                .ok_or_else(|| eyre::eyre!("Cannot convert from {}", from.symbol()))?;
+            let amount = parse_amount(amount, *from)?;
+            for unit in to {
+                println!("{}", convert(amount, fee_unit, *unit)?);
+            }
+        }
+        Commands::GasPrice(gas_price_commands) => match &gas_price_commands.command {
+            GasPriceSubCommands::History {
+                network,
+                gateway_url,
+                from,
+                to,
+                step,
+                format,
+                unit,
+                output,
+            } => {
+                let gateway_url = gateway_url.as_deref().unwrap_or(network.gateway_url());
+                let history = query_gas_price_history(*from, *to, *step, gateway_url)?;
+                let history = match format {
+                    HistoryFormat::Chart => history_to_chart(&history, *unit),
+                    HistoryFormat::Csv => history_to_csv(&history),
+                    HistoryFormat::Json => history_to_json(&history)?,
+                };
+                match output {
+                    Some(filename) => std::fs::write(filename, history)?,
+                    None => println!("{}", history),
+                }
+            }
+            GasPriceSubCommands::Watch {
+                network,
+                gateway_url,
+                interval,
+                dimension,
+                unit,
+                below,
+                above,
+                exec,
+                webhook,
+                exit,
+            } => {
+                let gateway_url = gateway_url.as_deref().unwrap_or(network.gateway_url());
+                let threshold = match (below, above) {
+                    (Some(price), _) => Some(Threshold::Below(*price)),
+                    (None, Some(price)) => Some(Threshold::Above(*price)),
+                    (None, None) => None,
+                };
+                let hooks: Vec<WatchHook> = exec
+                    .iter()
+                    .map(|command| WatchHook::Command(command.clone()))
+                    .chain(webhook.iter().map(|url| WatchHook::Webhook(url.clone())))
+                    .collect();
+                let mut last_gas_price = None;
+                let mut crossed = false;
+                watch_gas_price(gateway_url, Duration::from_secs(*interval), |sample| {
+                    let gas_price = sample.gas_prices.price(*dimension).in_unit(*unit);
+                    if last_gas_price != Some(gas_price) {
+                        println!(
+                            "block {}: {} price {} {}",
+                            sample.block_number,
+                            dimension.name(),
+                            gas_price,
+                            unit.name()
+                        );
+                        last_gas_price = Some(gas_price);
+                    }
+                    let is_crossed = threshold.is_some_and(|t| t.is_crossed(gas_price));
+                    let newly_crossed = is_crossed && !crossed;
+                    crossed = is_crossed;
+                    if !newly_crossed {
+                        return Ok(true);
+                    }
+                    if let Some(threshold) = threshold {
+                        println!(
+                            "block {}: {} price is {} {}",
+                            sample.block_number,
+                            dimension.name(),
+                            threshold,
+                            unit.name()
+                        );
+                    }
+                    for hook in &hooks {
+                        if let Err(e) = hook.fire(sample, gas_price) {
+                            eprintln!("failed to fire hook {:?}: {}", hook, e);
+                        }
+                    }
+                    Ok(!*exit && !hooks.is_empty())
+                })?;
             }
         },
     }
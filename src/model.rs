@@ -2,14 +2,240 @@ use std::str::FromStr;
 
 use ethers::types::U256;
 
+use crate::reconcile::{fee_token_address, Transfer};
 use crate::resources::CairoResources;
 use eyre::Result;
+use json::JsonValue;
 use jsonp::Pointer;
+use serde::Serialize;
+
+/// The token a fee or a gas price is denominated in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum FeeUnit {
+    /// Fees paid in ETH, priced in wei.
+    Wei,
+    /// Fees paid in STRK, priced in fri.
+    Fri,
+}
+
+impl FeeUnit {
+    /// Returns the name of the smallest unit of the token paying the fee.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FeeUnit::Wei => "wei",
+            FeeUnit::Fri => "fri",
+        }
+    }
+
+    /// Returns the symbol of the token paying the fee.
+    pub fn token_symbol(&self) -> &'static str {
+        match self {
+            FeeUnit::Wei => "ETH",
+            FeeUnit::Fri => "STRK",
+        }
+    }
+}
+
+/// The price of one gas unit, in both fee tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ResourcePrice {
+    pub price_in_wei: U256,
+    pub price_in_fri: U256,
+}
+
+impl ResourcePrice {
+    /// Returns the price denominated in `unit`.
+    pub fn in_unit(&self, unit: FeeUnit) -> U256 {
+        match unit {
+            FeeUnit::Wei => self.price_in_wei,
+            FeeUnit::Fri => self.price_in_fri,
+        }
+    }
+}
+
+/// The gas prices published by a block, one per fee market dimension.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct GasPrices {
+    pub l1_gas: ResourcePrice,
+    pub l1_data_gas: ResourcePrice,
+    pub l2_gas: ResourcePrice,
+}
+
+impl GasPrices {
+    /// Returns the gas price of a single dimension.
+    pub fn price(&self, dimension: GasDimension) -> ResourcePrice {
+        match dimension {
+            GasDimension::L1Gas => self.l1_gas,
+            GasDimension::L1DataGas => self.l1_data_gas,
+            GasDimension::L2Gas => self.l2_gas,
+        }
+    }
+}
+
+/// A fee market dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GasDimension {
+    L1Gas,
+    L1DataGas,
+    L2Gas,
+}
+
+impl GasDimension {
+    /// Returns the name of the dimension, as it reads in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GasDimension::L1Gas => "l1 gas",
+            GasDimension::L1DataGas => "l1 data gas",
+            GasDimension::L2Gas => "l2 gas",
+        }
+    }
+}
+
+/// The gas consumed by a transaction, one per fee market dimension.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GasConsumed {
+    pub l1_gas: U256,
+    pub l1_data_gas: U256,
+    pub l2_gas: U256,
+}
+
+/// The gas units a transaction paid for, as the ratio of its fee to the gas price.
+/// Keeping both terms preserves the remainder of the division.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticFee {
+    pub actual_fee: U256,
+    pub gas_price: U256,
+}
+
+impl StaticFee {
+    /// Returns the whole gas units paid for.
+    pub fn gas_units(&self) -> U256 {
+        if self.gas_price.is_zero() {
+            U256::zero()
+        } else {
+            self.actual_fee / self.gas_price
+        }
+    }
+
+    /// Returns the part of the fee which doesn't amount to a whole gas unit.
+    pub fn remainder(&self) -> U256 {
+        if self.gas_price.is_zero() {
+            U256::zero()
+        } else {
+            self.actual_fee % self.gas_price
+        }
+    }
+}
+
+impl std::fmt::Display for StaticFee {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.remainder().is_zero() {
+            write!(f, "{} gas", self.gas_units())
+        } else {
+            write!(
+                f,
+                "{} + {}/{} gas",
+                self.gas_units(),
+                self.remainder(),
+                self.gas_price
+            )
+        }
+    }
+}
 
 /// A transaction.
 #[derive(Debug)]
 pub struct Transaction(pub String);
 
+/// Returns the transaction itself, which the feeder gateway nests in a `transaction` field.
+fn unwrap_transaction(j: &JsonValue) -> &JsonValue {
+    if j.has_key("transaction") {
+        &j["transaction"]
+    } else {
+        j
+    }
+}
+
+impl Transaction {
+    /// Returns the account which sent the transaction.
+    /// The feeder gateway nests the transaction, and legacy invocations name their account `contract_address`.
+    /// # Returns
+    /// `None` if the transaction has no sender, such as L1 handler and legacy deploy transactions.
+    pub fn sender_address(&self) -> Result<Option<String>> {
+        let j = json::parse(&self.0)?;
+        let tx = unwrap_transaction(&j);
+        let sender = match tx["type"].as_str() {
+            Some("L1_HANDLER" | "DEPLOY") => None,
+            _ => tx["sender_address"]
+                .as_str()
+                .or_else(|| tx["contract_address"].as_str()),
+        };
+        Ok(sender.map(String::from))
+    }
+
+    /// Returns the contract and entrypoint the transaction invoked.
+    /// Account invocations are attributed to their first call, whose target and selector come first
+    /// in the calldata of both Cairo 0 and Cairo 1 accounts.
+    /// # Returns
+    /// `None` if the transaction invoked no entrypoint, such as declarations and deployments.
+    pub fn entry_point(&self) -> Result<Option<(String, String)>> {
+        let j = json::parse(&self.0)?;
+        let tx = unwrap_transaction(&j);
+        let selector = tx["entry_point_selector"].as_str();
+        let is_execute = selector.and_then(|selector| U256::from_str(selector).ok())
+            == Some(crate::abi::selector("__execute__"));
+        let entry_point = match (tx["type"].as_str(), selector) {
+            (Some("INVOKE_FUNCTION" | "INVOKE"), Some(selector)) if !is_execute => {
+                tx["contract_address"].as_str().zip(Some(selector))
+            }
+            (Some("INVOKE_FUNCTION" | "INVOKE"), _) => {
+                tx["calldata"][1].as_str().zip(tx["calldata"][2].as_str())
+            }
+            (Some("L1_HANDLER"), Some(selector)) => {
+                tx["contract_address"].as_str().zip(Some(selector))
+            }
+            (_, _) => None,
+        };
+        Ok(entry_point.map(|(contract, selector)| (contract.to_string(), selector.to_string())))
+    }
+
+    /// Returns the number of felts in the transaction calldata, or in the constructor calldata of a deployment.
+    /// # Returns
+    /// `None` if the transaction has no calldata, such as declarations.
+    pub fn calldata_length(&self) -> Result<Option<usize>> {
+        let j = json::parse(&self.0)?;
+        let tx = unwrap_transaction(&j);
+        let calldata = if tx.has_key("calldata") {
+            &tx["calldata"]
+        } else {
+            &tx["constructor_calldata"]
+        };
+        Ok(calldata.is_array().then(|| calldata.len()))
+    }
+
+    /// Returns the hash of the class a transaction declared.
+    /// # Returns
+    /// `None` if the transaction is not a declaration.
+    pub fn declared_class_hash(&self) -> Result<Option<String>> {
+        let j = json::parse(&self.0)?;
+        let tx = unwrap_transaction(&j);
+        Ok(match tx["type"].as_str() {
+            Some("DECLARE") => tx["class_hash"].as_str().map(String::from),
+            _ => None,
+        })
+    }
+
+    /// Returns the transaction type, such as `INVOKE_FUNCTION` or `DECLARE`.
+    pub fn transaction_type(&self) -> Result<String> {
+        let j = json::parse(&self.0)?;
+        let tx = unwrap_transaction(&j);
+        tx["type"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| eyre::eyre!("Missing type in transaction"))
+    }
+}
+
 /// A transaction receipt.
 #[derive(Debug)]
 pub struct TransactionReceipt(pub String);
@@ -22,53 +248,342 @@ impl TransactionReceipt {
     }
 
     /// Returns the transaction actual fee.
+    /// Both the feeder gateway format (a hex string) and the RPC format
+    /// (an `amount` and `unit` object) are supported.
     /// # Returns
     /// The transaction actual fee.
     pub fn actual_fee(&self) -> Result<U256> {
-        let p = Pointer::default();
-        let raw = self.0.as_str();
-        let actual_fee: &str = p.dotted(raw, ".actual_fee")?;
-        Ok(U256::from_str(actual_fee)?)
+        let j = json::parse(&self.0)?;
+        let actual_fee = &j["actual_fee"];
+        let amount = if actual_fee.is_object() {
+            &actual_fee["amount"]
+        } else {
+            actual_fee
+        };
+        parse_u256(amount).ok_or_else(|| eyre::eyre!("Missing or invalid actual fee in receipt"))
+    }
+
+    /// Returns the hash of the transaction.
+    pub fn transaction_hash(&self) -> Result<String> {
+        let j = json::parse(&self.0)?;
+        j["transaction_hash"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| eyre::eyre!("Missing transaction hash in receipt"))
+    }
+
+    /// Returns the number of the block which included the transaction.
+    /// # Returns
+    /// `None` if the transaction is still pending.
+    pub fn block_number(&self) -> Result<Option<u64>> {
+        let j = json::parse(&self.0)?;
+        Ok(j["block_number"].as_u64())
+    }
+
+    /// Returns the unit the actual fee is denominated in.
+    /// The RPC reports it, while the feeder gateway doesn't: the unit is then the one of the fee token
+    /// which emitted the last transfer, the fee transfer.
+    /// L1 handlers, whose fee is paid on L1, and transactions which paid no fee are in wei.
+    /// # Errors
+    /// If the receipt has no unit and no fee transfer to tell it.
+    pub fn fee_unit(&self) -> Result<FeeUnit> {
+        let j = json::parse(&self.0)?;
+        match j["actual_fee"]["unit"].as_str() {
+            Some("FRI") => return Ok(FeeUnit::Fri),
+            Some("WEI") => return Ok(FeeUnit::Wei),
+            Some(unit) => return Err(eyre::eyre!("Unknown fee unit: {}", unit)),
+            None => {}
+        }
+        if j.has_key("l1_to_l2_consumed_message") || self.actual_fee()?.is_zero() {
+            return Ok(FeeUnit::Wei);
+        }
+        self.events()?
+            .iter()
+            .rev()
+            .filter(|event| Transfer::try_from_event(event).is_some())
+            .find_map(|event| {
+                [FeeUnit::Wei, FeeUnit::Fri].into_iter().find(|&fee_unit| {
+                    same_address(&event.from_address, fee_token_address(fee_unit))
+                })
+            })
+            .ok_or_else(|| eyre::eyre!("Unknown fee unit: no unit nor fee transfer in receipt"))
+    }
+
+    /// Returns the gas consumed by the transaction on each fee market dimension.
+    /// # Returns
+    /// `None` if the receipt predates the per-dimension gas accounting.
+    pub fn gas_consumed(&self) -> Result<Option<GasConsumed>> {
+        let j = json::parse(&self.0)?;
+        let exec_resources = &j["execution_resources"];
+        // The feeder gateway nests the totals, the RPC flattens them.
+        let gas = if exec_resources.has_key("total_gas_consumed") {
+            &exec_resources["total_gas_consumed"]
+        } else {
+            exec_resources
+        };
+
+        if !gas.has_key("l1_gas") {
+            return Ok(None);
+        }
+        Ok(Some(GasConsumed {
+            l1_gas: parse_u256(&gas["l1_gas"]).unwrap_or_default(),
+            l1_data_gas: parse_u256(&gas["l1_data_gas"]).unwrap_or_default(),
+            l2_gas: parse_u256(&gas["l2_gas"]).unwrap_or_default(),
+        }))
+    }
+
+    /// Returns the events emitted by the transaction.
+    pub fn events(&self) -> Result<Vec<Event>> {
+        let j = json::parse(&self.0)?;
+        let strings = |values: &JsonValue| {
+            values
+                .members()
+                .filter_map(|value| value.as_str().map(String::from))
+                .collect()
+        };
+        j["events"]
+            .members()
+            .map(|event| {
+                Ok(Event {
+                    from_address: event["from_address"]
+                        .as_str()
+                        .ok_or_else(|| eyre::eyre!("Missing emitter address in event"))?
+                        .to_string(),
+                    keys: strings(&event["keys"]),
+                    data: strings(&event["data"]),
+                })
+            })
+            .collect()
     }
 
     /// Returns the resources used in this transaction.
     pub fn resources_used(&self) -> Result<CairoResources> {
-        let j = json::parse(&self.0).unwrap();
-        let exec_resources = &j["execution_resources"];
-        let instance_counter = &exec_resources["builtin_instance_counter"];
-
-        let category = "calls";
-        let steps = exec_resources["n_steps"].as_f32().unwrap_or(0.0);
-        let range_check = instance_counter["range_check_builtin"]
-            .as_f32()
-            .unwrap_or(0.0);
-        let pedersen = instance_counter["pedersen_builtin"].as_f32().unwrap_or(0.0);
-        let bitwise = instance_counter["bitwise_builtin"].as_f32().unwrap_or(0.0);
-        let ecdsa = instance_counter["ecdsa_builtin"].as_f32().unwrap_or(0.0);
-        let ec_op = instance_counter["ec_op_builtin"].as_f32().unwrap_or(0.0);
-        Ok(CairoResources::new(
-            category,
-            steps,
-            pedersen,
-            range_check,
-            ecdsa,
-            bitwise,
-            ec_op,
+        let j = json::parse(&self.0)?;
+        Ok(parse_execution_resources(
+            &j["execution_resources"],
+            "calls",
         ))
     }
 }
 
+/// An event emitted by a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub from_address: String,
+    /// The event keys, the first one being the event selector.
+    pub keys: Vec<String>,
+    pub data: Vec<String>,
+}
+
+/// A transaction trace, with the validate, execute and fee transfer invocations.
+#[derive(Debug)]
+pub struct TransactionTrace(pub String);
+
+impl TransactionTrace {
+    /// Attempts to create a TransactionTrace from a file.
+    pub fn try_from_file(filename: &str) -> Result<Self> {
+        let s = std::fs::read_to_string(filename)?;
+        Ok(Self(s))
+    }
+}
+
+/// Parse the steps and builtins of `execution_resources`.
+/// The feeder gateway nests the builtins in a `builtin_instance_counter`,
+/// while the RPC names them `<builtin>_builtin_applications`.
+pub(crate) fn parse_execution_resources(
+    exec_resources: &JsonValue,
+    category: &'static str,
+) -> CairoResources {
+    if !exec_resources.has_key("n_steps") && exec_resources.has_key("steps") {
+        let builtin = |name: &str| {
+            exec_resources[format!("{name}_builtin_applications").as_str()]
+                .as_f32()
+                .unwrap_or(0.0)
+        };
+        return CairoResources::new(
+            category,
+            exec_resources["steps"].as_f32().unwrap_or(0.0),
+            builtin("pedersen"),
+            builtin("range_check"),
+            builtin("ecdsa"),
+            builtin("bitwise"),
+            builtin("ec_op"),
+        );
+    }
+    let instance_counter = &exec_resources["builtin_instance_counter"];
+
+    let steps = exec_resources["n_steps"].as_f32().unwrap_or(0.0);
+    let range_check = instance_counter["range_check_builtin"]
+        .as_f32()
+        .unwrap_or(0.0);
+    let pedersen = instance_counter["pedersen_builtin"].as_f32().unwrap_or(0.0);
+    let bitwise = instance_counter["bitwise_builtin"].as_f32().unwrap_or(0.0);
+    let ecdsa = instance_counter["ecdsa_builtin"].as_f32().unwrap_or(0.0);
+    let ec_op = instance_counter["ec_op_builtin"].as_f32().unwrap_or(0.0);
+    CairoResources::new(
+        category,
+        steps,
+        pedersen,
+        range_check,
+        ecdsa,
+        bitwise,
+        ec_op,
+    )
+}
+
 #[derive(Debug)]
 pub struct Block(pub String);
 
 impl Block {
-    /// Returns the block gas price.
+    /// Attempts to create a Block from a file.
+    pub fn try_from_file(filename: &str) -> Result<Self> {
+        let s = std::fs::read_to_string(filename)?;
+        Ok(Self(s))
+    }
+
+    /// Returns the block number.
+    pub fn block_number(&self) -> Result<u64> {
+        let j = json::parse(&self.0)?;
+        j["block_number"]
+            .as_u64()
+            .ok_or_else(|| eyre::eyre!("Missing block number in block"))
+    }
+
+    /// Returns the StarkNet version the block was produced with, if published.
+    pub fn starknet_version(&self) -> Result<Option<String>> {
+        let j = json::parse(&self.0)?;
+        Ok(j["starknet_version"].as_str().map(String::from))
+    }
+
+    /// Returns the transactions included in the block.
+    pub fn transactions(&self) -> Result<Vec<Transaction>> {
+        let j = json::parse(&self.0)?;
+        Ok(j["transactions"]
+            .members()
+            .map(|tx| Transaction(tx.dump()))
+            .collect())
+    }
+
+    /// Returns the receipts of the transactions included in the block, in the same order.
+    pub fn transaction_receipts(&self) -> Result<Vec<TransactionReceipt>> {
+        let j = json::parse(&self.0)?;
+        Ok(j["transaction_receipts"]
+            .members()
+            .map(|tx_receipt| TransactionReceipt(tx_receipt.dump()))
+            .collect())
+    }
+
+    /// Returns the address of the sequencer which produced the block, and collected its fees.
+    pub fn sequencer_address(&self) -> Result<Option<String>> {
+        let j = json::parse(&self.0)?;
+        Ok(j["sequencer_address"].as_str().map(String::from))
+    }
+
+    /// Returns the block timestamp, in seconds since the Unix epoch.
+    pub fn timestamp(&self) -> Result<u64> {
+        let j = json::parse(&self.0)?;
+        j["timestamp"]
+            .as_u64()
+            .ok_or_else(|| eyre::eyre!("Missing timestamp in block"))
+    }
+
+    /// Returns the block L1 gas price in wei.
     /// # Returns
     /// The block gas price.
     pub fn gas_price(&self) -> Result<U256> {
         let p = Pointer::default();
         let raw = self.0.as_str();
-        let gas_used: &str = p.dotted(raw, ".gas_price")?;
-        Ok(U256::from_str(gas_used)?)
+        let gas_price: Result<&str, _> = p.dotted(raw, ".gas_price");
+        match gas_price {
+            Ok(gas_price) => Ok(U256::from_str(gas_price)?),
+            Err(_) => Ok(self.gas_prices()?.l1_gas.price_in_wei),
+        }
+    }
+
+    /// Returns the block gas prices for every fee market dimension.
+    /// Blocks predating StarkNet 0.13 only publish a single `gas_price`,
+    /// which is reported as the L1 gas price.
+    pub fn gas_prices(&self) -> Result<GasPrices> {
+        let j = json::parse(&self.0)?;
+        if j.has_key("l1_gas_price") {
+            return Ok(GasPrices {
+                l1_gas: parse_resource_price(&j["l1_gas_price"]),
+                l1_data_gas: parse_resource_price(&j["l1_data_gas_price"]),
+                l2_gas: parse_resource_price(&j["l2_gas_price"]),
+            });
+        }
+        let price_in_wei = parse_u256(&j["gas_price"])
+            .ok_or_else(|| eyre::eyre!("Missing or invalid gas price in block"))?;
+        Ok(GasPrices {
+            l1_gas: ResourcePrice {
+                price_in_wei,
+                price_in_fri: parse_u256(&j["strk_l1_gas_price"]).unwrap_or_default(),
+            },
+            ..Default::default()
+        })
+    }
+}
+
+/// Parses a `{ price_in_wei, price_in_fri }` object.
+fn parse_resource_price(value: &JsonValue) -> ResourcePrice {
+    ResourcePrice {
+        price_in_wei: parse_u256(&value["price_in_wei"]).unwrap_or_default(),
+        price_in_fri: parse_u256(&value["price_in_fri"]).unwrap_or_default(),
+    }
+}
+
+/// Returns whether two addresses are the same, however they are padded.
+pub(crate) fn same_address(a: &str, b: &str) -> bool {
+    match (U256::from_str(a), U256::from_str(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        (_, _) => a.eq_ignore_ascii_case(b),
+    }
+}
+
+/// Parses a JSON number, hex string or decimal string into a U256.
+pub(crate) fn parse_u256(value: &JsonValue) -> Option<U256> {
+    if let Some(n) = value.as_u64() {
+        return Some(U256::from(n));
+    }
+    parse_u256_str(value.as_str()?).ok()
+}
+
+/// Parses a decimal or `0x` prefixed hexadecimal string into a U256.
+pub(crate) fn parse_u256_str(s: &str) -> Result<U256> {
+    if s.starts_with("0x") {
+        Ok(U256::from_str(s)?)
+    } else {
+        Ok(U256::from_dec_str(s)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fee_unit_of_a_v1_receipt_is_wei() {
+        let receipt = TransactionReceipt::try_from_file("tests/example.json").unwrap();
+        assert_eq!(receipt.fee_unit().unwrap(), FeeUnit::Wei);
+    }
+
+    #[test]
+    fn fee_unit_of_a_v3_receipt_is_fri() {
+        let receipt = TransactionReceipt::try_from_file("tests/example_v3.json").unwrap();
+        assert_eq!(receipt.fee_unit().unwrap(), FeeUnit::Fri);
+    }
+
+    #[test]
+    fn fee_unit_is_read_from_rpc_receipts() {
+        let receipt = TransactionReceipt(
+            r#"{"actual_fee": {"amount": "0x10", "unit": "FRI"}, "events": []}"#.to_string(),
+        );
+        assert_eq!(receipt.fee_unit().unwrap(), FeeUnit::Fri);
+    }
+
+    #[test]
+    fn fee_unit_without_fee_transfer_is_an_error() {
+        let receipt = TransactionReceipt(r#"{"actual_fee": "0x10", "events": []}"#.to_string());
+        assert!(receipt.fee_unit().is_err());
     }
 }
@@ -0,0 +1,133 @@
+/// Public StarkNet networks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Network {
+    Mainnet,
+    Goerli,
+    Goerli2,
+    Sepolia,
+}
+
+impl Network {
+    /// Returns the network feeder gateway URL.
+    pub fn gateway_url(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "https://alpha-mainnet.starknet.io/feeder_gateway",
+            Network::Goerli => "https://alpha4.starknet.io/feeder_gateway",
+            Network::Goerli2 => "https://alpha4-2.starknet.io/feeder_gateway",
+            Network::Sepolia => "https://alpha-sepolia.starknet.io/feeder_gateway",
+        }
+    }
+
+    /// Returns the network chain id.
+    pub fn chain_id(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "SN_MAIN",
+            Network::Goerli => "SN_GOERLI",
+            Network::Goerli2 => "SN_GOERLI2",
+            Network::Sepolia => "SN_SEPOLIA",
+        }
+    }
+
+    /// Returns the execution limits the network enforces on transactions.
+    /// The public networks share the same limits, see [`STARKNET_EXECUTION_LIMITS`].
+    pub fn execution_limits(&self) -> ExecutionLimits {
+        STARKNET_EXECUTION_LIMITS
+    }
+
+    /// Returns the network served by a feeder gateway URL, if it is a public one.
+    pub fn from_gateway_url(network_gateway_url: &str) -> Option<Self> {
+        let url = network_gateway_url.trim_end_matches('/');
+        [
+            Network::Mainnet,
+            Network::Goerli,
+            Network::Goerli2,
+            Network::Sepolia,
+        ]
+        .into_iter()
+        .find(|network| network.gateway_url() == url)
+    }
+}
+
+/// The execution limits of the public StarkNet networks, the same on mainnet and testnets.
+/// See https://docs.starknet.io/tools/limits-and-triggers/ (StarkNet 0.13).
+pub const STARKNET_EXECUTION_LIMITS: ExecutionLimits = ExecutionLimits {
+    max_steps: 10_000_000,
+    max_calldata_length: 5_000,
+    max_class_size: 4_089_446,
+};
+
+/// The limits a network enforces on transactions, beyond which they are rejected or fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionLimits {
+    /// The maximum number of Cairo steps a transaction can run.
+    pub max_steps: u64,
+    /// The maximum number of felts in the calldata of a transaction.
+    pub max_calldata_length: usize,
+    /// The maximum size of a declared contract class, in bytes.
+    pub max_class_size: usize,
+}
+
+impl ExecutionLimits {
+    /// Returns the limits with some of them overridden, such as those of an appchain.
+    pub fn with_overrides(
+        self,
+        max_steps: Option<u64>,
+        max_calldata_length: Option<usize>,
+        max_class_size: Option<usize>,
+    ) -> Self {
+        Self {
+            max_steps: max_steps.unwrap_or(self.max_steps),
+            max_calldata_length: max_calldata_length.unwrap_or(self.max_calldata_length),
+            max_class_size: max_class_size.unwrap_or(self.max_class_size),
+        }
+    }
+
+    /// Returns how a transaction exceeds the limits, if it does.
+    /// Only the known sizes of the transaction are checked.
+    ///
+    /// # Arguments
+    ///
+    /// * `steps` - The Cairo steps the transaction ran, including its validation and fee transfer.
+    /// * `calldata_length` - The number of felts in the transaction calldata.
+    /// * `class_size` - The size of the class the transaction declared, in bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use howmuch_rs::network::Network;
+    ///
+    /// let limits = Network::Mainnet.execution_limits();
+    /// assert!(limits.violations(Some(3_000), Some(12), None).is_empty());
+    /// assert_eq!(
+    ///     limits.violations(Some(12_000_000), None, None),
+    ///     vec!["ran 12000000 steps, over the limit of 10000000 steps per transaction"]
+    /// );
+    /// ```
+    pub fn violations(
+        &self,
+        steps: Option<u64>,
+        calldata_length: Option<usize>,
+        class_size: Option<usize>,
+    ) -> Vec<String> {
+        let mut violations = vec![];
+        if let Some(steps) = steps.filter(|&steps| steps > self.max_steps) {
+            violations.push(format!(
+                "ran {steps} steps, over the limit of {} steps per transaction",
+                self.max_steps
+            ));
+        }
+        if let Some(length) = calldata_length.filter(|&length| length > self.max_calldata_length) {
+            violations.push(format!(
+                "has {length} calldata felts, over the limit of {}",
+                self.max_calldata_length
+            ));
+        }
+        if let Some(size) = class_size.filter(|&size| size > self.max_class_size) {
+            violations.push(format!(
+                "declares a class of {size} bytes, over the limit of {} bytes",
+                self.max_class_size
+            ));
+        }
+        violations
+    }
+}
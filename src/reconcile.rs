@@ -0,0 +1,348 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ethers::types::U256;
+use eyre::Result;
+
+use crate::abi::selector;
+use crate::model::{same_address, Block, Event, FeeUnit, Transaction, TransactionReceipt};
+use crate::resources::get_transaction_receipt;
+use crate::{query_block, query_tx};
+
+/// The address of the ETH fee token, on every public network.
+pub const ETH_FEE_TOKEN_ADDRESS: &str =
+    "0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7";
+/// The address of the STRK fee token, on every public network.
+pub const STRK_FEE_TOKEN_ADDRESS: &str =
+    "0x04718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d";
+
+/// Returns the address of the token paying fees in a unit.
+pub fn fee_token_address(fee_unit: FeeUnit) -> &'static str {
+    match fee_unit {
+        FeeUnit::Wei => ETH_FEE_TOKEN_ADDRESS,
+        FeeUnit::Fri => STRK_FEE_TOKEN_ADDRESS,
+    }
+}
+
+/// An ERC20 transfer, decoded from its `Transfer` event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transfer {
+    pub token: String,
+    pub from: String,
+    pub to: String,
+    pub amount: U256,
+}
+
+impl Transfer {
+    /// Decode a `Transfer` event.
+    /// Cairo 0 tokens put the addresses and the amount in the data,
+    /// Cairo 1 tokens key the event by the addresses.
+    /// The amount is a `Uint256`, split in its low and high 128 bits.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the event is not a transfer.
+    pub fn try_from_event(event: &Event) -> Option<Self> {
+        let key = event.keys.first()?;
+        if U256::from_str(key).ok()? != selector("Transfer") {
+            return None;
+        }
+        let fields: Vec<&String> = event.keys[1..].iter().chain(event.data.iter()).collect();
+        if fields.len() != 4 {
+            return None;
+        }
+        let (low, high) = (
+            U256::from_str(fields[2]).ok()?,
+            U256::from_str(fields[3]).ok()?,
+        );
+        if low.bits() > 128 || high.bits() > 128 {
+            return None;
+        }
+        Some(Self {
+            token: event.from_address.clone(),
+            from: fields[0].clone(),
+            to: fields[1].clone(),
+            amount: (high << 128) | low,
+        })
+    }
+}
+
+/// The actual fee of a transaction, checked against the transfer which paid it.
+#[derive(Debug, Clone)]
+pub struct FeeReconciliation {
+    pub actual_fee: U256,
+    pub fee_unit: FeeUnit,
+    /// The transfer of the fee to the sequencer, the last transfer of the fee token.
+    pub fee_transfer: Option<Transfer>,
+    /// The account which sent the transaction, when known.
+    pub sender: Option<String>,
+    /// Transfers of the fee token to the fee payer, from another account, such as a paymaster.
+    pub sponsors: Vec<Transfer>,
+    /// Everything which doesn't reconcile.
+    pub issues: Vec<String>,
+}
+
+impl FeeReconciliation {
+    /// Returns whether the fee transfer matches the actual fee.
+    pub fn is_reconciled(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns the account which paid the fee.
+    pub fn payer(&self) -> Option<&str> {
+        self.fee_transfer
+            .as_ref()
+            .map(|transfer| transfer.from.as_str())
+    }
+}
+
+impl fmt::Display for FeeReconciliation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let unit = self.fee_unit.name();
+        writeln!(f, "{:<22}{} {unit}", "Actual fee:", self.actual_fee)?;
+        match &self.fee_transfer {
+            Some(transfer) => {
+                writeln!(f, "{:<22}{} {unit}", "Fee transfer:", transfer.amount)?;
+                writeln!(f, "{:<22}{}", "Paid by:", transfer.from)?;
+                writeln!(f, "{:<22}{}", "Paid to:", transfer.to)?;
+            }
+            None => writeln!(f, "{:<22}none", "Fee transfer:")?,
+        }
+        if let Some(sender) = &self.sender {
+            writeln!(f, "{:<22}{}", "Sender:", sender)?;
+        }
+        for sponsor in &self.sponsors {
+            writeln!(
+                f,
+                "{:<22}{} ({} {unit})",
+                "Sponsored by:", sponsor.from, sponsor.amount
+            )?;
+        }
+        if self.is_reconciled() {
+            writeln!(f, "{:<22}yes", "Reconciled:")
+        } else {
+            writeln!(f, "{:<22}no", "Reconciled:")?;
+            for issue in &self.issues {
+                writeln!(f, "  - {issue}")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Reconcile the actual fee of a transaction against the fee token transfer it emitted.
+///
+/// # Arguments
+///
+/// * `tx_receipt` - The transaction receipt.
+/// * `transaction` - The transaction, to compare the fee payer with its sender.
+/// * `sequencer_address` - The sequencer of the block which included the transaction, which must be paid the fee.
+///
+/// # Example
+///
+/// ```
+/// use howmuch_rs::model::TransactionReceipt;
+/// use howmuch_rs::reconcile::reconcile_fee;
+///
+/// let tx_receipt = TransactionReceipt::try_from_file("tests/example.json").unwrap();
+/// let reconciliation = reconcile_fee(&tx_receipt, None, None).unwrap();
+/// assert!(reconciliation.is_reconciled());
+/// assert_eq!(
+///     reconciliation.payer(),
+///     Some("0x77d5345916a48b88ad002ead40344edbaa3a900d66c40d0b8e0c4c9f529d9a6")
+/// );
+/// ```
+pub fn reconcile_fee(
+    tx_receipt: &TransactionReceipt,
+    transaction: Option<&Transaction>,
+    sequencer_address: Option<&str>,
+) -> Result<FeeReconciliation> {
+    let actual_fee = tx_receipt.actual_fee()?;
+    let fee_unit = tx_receipt.fee_unit()?;
+    let token = fee_token_address(fee_unit);
+    let transfers: Vec<Transfer> = tx_receipt
+        .events()?
+        .iter()
+        .filter_map(Transfer::try_from_event)
+        .filter(|transfer| same_address(&transfer.token, token))
+        .collect();
+    let sender = match transaction {
+        Some(transaction) => transaction.sender_address()?,
+        None => None,
+    };
+
+    let mut issues = vec![];
+    // The fee is transferred once the transaction is executed, so it is the last transfer.
+    let fee_transfer = if actual_fee.is_zero() {
+        None
+    } else {
+        transfers.last().cloned()
+    };
+
+    let mut sponsors = vec![];
+    match &fee_transfer {
+        None if !actual_fee.is_zero() => issues.push(format!(
+            "No {} transfer found for the actual fee",
+            fee_unit.token_symbol()
+        )),
+        None => {}
+        Some(fee_transfer) => {
+            if fee_transfer.amount != actual_fee {
+                issues.push(format!(
+                    "The fee transfer of {} {} doesn't match the actual fee of {} {}",
+                    fee_transfer.amount,
+                    fee_unit.name(),
+                    actual_fee,
+                    fee_unit.name()
+                ));
+            }
+            if let Some(sender) = &sender {
+                if !same_address(&fee_transfer.from, sender) {
+                    issues.push(format!(
+                        "The fee was paid by {}, not by the sender {}",
+                        fee_transfer.from, sender
+                    ));
+                }
+            }
+            if let Some(sequencer_address) = sequencer_address {
+                if !same_address(&fee_transfer.to, sequencer_address) {
+                    issues.push(format!(
+                        "The fee was paid to {}, not to the sequencer {}",
+                        fee_transfer.to, sequencer_address
+                    ));
+                }
+            }
+            sponsors = transfers[..transfers.len() - 1]
+                .iter()
+                .filter(|transfer| {
+                    same_address(&transfer.to, &fee_transfer.from)
+                        && !same_address(&transfer.from, &fee_transfer.from)
+                })
+                .cloned()
+                .collect();
+        }
+    }
+
+    Ok(FeeReconciliation {
+        actual_fee,
+        fee_unit,
+        fee_transfer,
+        sender,
+        sponsors,
+        issues,
+    })
+}
+
+/// Same as [`reconcile_fee`], reading the receipt and block from files, or querying them from a network.
+/// The transaction is only queried, and its sender checked, if its hash is provided.
+pub fn get_fee_reconciliation(
+    tx_hash: Option<&str>,
+    network_gateway_url: &str,
+    transaction_file: Option<&str>,
+    block_file: Option<&str>,
+) -> Result<FeeReconciliation> {
+    let tx_receipt = get_transaction_receipt(tx_hash, Some(network_gateway_url), transaction_file)?;
+    let transaction = match tx_hash {
+        Some(tx_hash) => Some(query_tx(tx_hash, network_gateway_url)?),
+        None => None,
+    };
+    let block = match (block_file, tx_receipt.block_number()?) {
+        (Some(filename), _) => Some(Block::try_from_file(filename)?),
+        (None, Some(block_number)) => {
+            Some(query_block(&block_number.to_string(), network_gateway_url)?)
+        }
+        (None, None) => None,
+    };
+    let sequencer_address = match &block {
+        Some(block) => block.sequencer_address()?,
+        None => None,
+    };
+    reconcile_fee(
+        &tx_receipt,
+        transaction.as_ref(),
+        sequencer_address.as_deref(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSFER_KEY: &str = "0x99cd8bde557814842a3121e8ddfd433a539b8c9f14bf31ebf108d12e6196e9";
+
+    fn event(keys: &[&str], data: &[&str]) -> Event {
+        Event {
+            from_address: ETH_FEE_TOKEN_ADDRESS.to_string(),
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+            data: data.iter().map(|value| value.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn transfer_from_cairo_0_event() {
+        let transfer =
+            Transfer::try_from_event(&event(&[TRANSFER_KEY], &["0x1", "0x2", "0x3", "0x1"]))
+                .unwrap();
+        assert_eq!(transfer.token, ETH_FEE_TOKEN_ADDRESS);
+        assert_eq!(transfer.from, "0x1");
+        assert_eq!(transfer.to, "0x2");
+        assert_eq!(transfer.amount, (U256::one() << 128) + 3);
+    }
+
+    #[test]
+    fn transfer_from_cairo_1_event() {
+        let transfer =
+            Transfer::try_from_event(&event(&[TRANSFER_KEY, "0x1", "0x2"], &["0x3", "0x0"]))
+                .unwrap();
+        assert_eq!(transfer.from, "0x1");
+        assert_eq!(transfer.to, "0x2");
+        assert_eq!(transfer.amount, U256::from(3));
+    }
+
+    #[test]
+    fn transfer_from_malformed_events() {
+        // Not a transfer.
+        assert!(
+            Transfer::try_from_event(&event(&["0x1"], &["0x1", "0x2", "0x3", "0x0"])).is_none()
+        );
+        // No key at all.
+        assert!(Transfer::try_from_event(&event(&[], &["0x1", "0x2", "0x3", "0x0"])).is_none());
+        // Too short.
+        assert!(
+            Transfer::try_from_event(&event(&[TRANSFER_KEY], &["0x1", "0x2", "0x3"])).is_none()
+        );
+        // Not a number.
+        assert!(Transfer::try_from_event(&event(
+            &[TRANSFER_KEY],
+            &["0x1", "0x2", "amount", "0x0"]
+        ))
+        .is_none());
+        // Not a Uint256.
+        let over_128_bits = "0x100000000000000000000000000000000";
+        assert!(Transfer::try_from_event(&event(
+            &[TRANSFER_KEY],
+            &["0x1", "0x2", over_128_bits, "0x0"]
+        ))
+        .is_none());
+    }
+
+    #[test]
+    fn reconcile_v1_receipt() {
+        let receipt = TransactionReceipt::try_from_file("tests/example.json").unwrap();
+        let reconciliation = reconcile_fee(&receipt, None, None).unwrap();
+        assert_eq!(reconciliation.fee_unit, FeeUnit::Wei);
+        assert!(reconciliation.is_reconciled(), "{reconciliation}");
+    }
+
+    #[test]
+    fn reconcile_v3_receipt() {
+        let receipt = TransactionReceipt::try_from_file("tests/example_v3.json").unwrap();
+        let reconciliation = reconcile_fee(&receipt, None, None).unwrap();
+        assert_eq!(reconciliation.fee_unit, FeeUnit::Fri);
+        assert!(reconciliation.is_reconciled(), "{reconciliation}");
+        assert_eq!(
+            reconciliation.payer(),
+            Some("0x77d5345916a48b88ad002ead40344edbaa3a900d66c40d0b8e0c4c9f529d9a6")
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use ethers::utils::keccak256;
+use eyre::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::setting::Setting;
+
+/// Whether [`crate::http_get`] records responses to, or replays them from, a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayMode {
+    /// Responses are fetched from the network.
+    Off,
+    /// Responses are fetched from the network and stored in the directory.
+    Record(PathBuf),
+    /// Responses are served from the directory, the network is never hit.
+    Replay(PathBuf),
+}
+
+static REPLAY_MODE: Setting<ReplayMode> = Setting::new(ReplayMode::Off);
+
+/// Sets whether responses are recorded or replayed. Both are off by default.
+pub fn set_replay_mode(mode: ReplayMode) {
+    REPLAY_MODE.set(mode);
+}
+
+/// A recorded response, stored as `<keccak of the url>.json`.
+#[derive(Debug, Serialize, Deserialize)]
+struct Recording {
+    url: String,
+    body: String,
+}
+
+fn recording_path(dir: &std::path::Path, url: &str) -> PathBuf {
+    let hash: String = keccak256(url.as_bytes())[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    dir.join(format!("{hash}.json"))
+}
+
+/// Returns the recorded response to a GET request when replaying.
+/// # Returns
+/// `None` when not replaying, an error when the response was not recorded.
+pub fn replay(url: &str) -> Option<Result<String>> {
+    let dir = match REPLAY_MODE.get() {
+        ReplayMode::Replay(dir) => dir,
+        _ => return None,
+    };
+    let path = recording_path(&dir, url);
+    debug!("replaying {} from {}", url, path.display());
+    let recording = std::fs::read_to_string(&path)
+        .map_err(|e| eyre::eyre!("No recorded response for {}: {}", url, e))
+        .and_then(|s| Ok(serde_json::from_str::<Recording>(&s)?));
+    Some(recording.map(|recording| recording.body))
+}
+
+/// Records the response to a GET request when recording.
+/// # Errors
+/// If the recording can't be written, since the recorded fixtures would be incomplete.
+pub fn record(url: &str, body: &str) -> Result<()> {
+    let dir = match REPLAY_MODE.get() {
+        ReplayMode::Record(dir) => dir,
+        _ => return Ok(()),
+    };
+    let recording = Recording {
+        url: url.to_string(),
+        body: body.to_string(),
+    };
+    let path = recording_path(&dir, url);
+    std::fs::create_dir_all(&dir)
+        .map_err(eyre::Report::from)
+        .and_then(|_| Ok(serde_json::to_string_pretty(&recording)?))
+        .and_then(|s| Ok(std::fs::write(&path, s)?))
+        .map_err(|e| eyre::eyre!("Failed to record {} to {}: {}", url, path.display(), e))
+}
@@ -1,13 +1,53 @@
-use crate::model::TransactionReceipt;
-use crate::query_tx_receipt;
+use crate::currencies::{format_dollar_cost_at, get_token_price};
+use crate::model::{Block, FeeUnit, GasConsumed, TransactionReceipt};
+use crate::units::{format_amount, Unit};
+use crate::{compute_static_tx_fee, query_block, query_tx_receipt};
+use ethers::types::U256;
 use eyre::Result;
+use log::warn;
 use tabled::Tabled;
 use tabled::{Alignment, Panel, Style, Table};
 
 pub type Weights = CairoResources;
 
+/// The resources a transaction can use, as named on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Resource {
+    Steps,
+    Pedersen,
+    #[value(alias = "range_check")]
+    RangeCheck,
+    Ecdsa,
+    Bitwise,
+    #[value(alias = "ec_op")]
+    EcOp,
+}
+
+impl Resource {
+    pub const ALL: [Resource; 6] = [
+        Resource::Steps,
+        Resource::Pedersen,
+        Resource::RangeCheck,
+        Resource::Ecdsa,
+        Resource::Bitwise,
+        Resource::EcOp,
+    ];
+
+    /// Returns the resource name, as displayed in the summary table.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Resource::Steps => "steps",
+            Resource::Pedersen => "pedersen",
+            Resource::RangeCheck => "range_check",
+            Resource::Ecdsa => "ecdsa",
+            Resource::Bitwise => "bitwise",
+            Resource::EcOp => "ec_op",
+        }
+    }
+}
+
 /// List of all the different resources a transaction can use
-#[derive(Tabled, Copy, Clone)]
+#[derive(Tabled, Debug, Copy, Clone)]
 pub struct CairoResources {
     pub category: &'static str,
     pub steps: f32,
@@ -65,70 +105,274 @@ impl CairoResources {
         .unwrap_or(&0.0)
     }
 
+    /// Returns the amount of a resource.
+    pub fn get(&self, resource: Resource) -> f32 {
+        match resource {
+            Resource::Steps => self.steps,
+            Resource::Pedersen => self.pedersen,
+            Resource::RangeCheck => self.range_check,
+            Resource::Ecdsa => self.ecdsa,
+            Resource::Bitwise => self.bitwise,
+            Resource::EcOp => self.ec_op,
+        }
+    }
+
+    /// Sets the amount of a resource.
+    pub fn set(&mut self, resource: Resource, amount: f32) {
+        match resource {
+            Resource::Steps => self.steps = amount,
+            Resource::Pedersen => self.pedersen = amount,
+            Resource::RangeCheck => self.range_check = amount,
+            Resource::Ecdsa => self.ecdsa = amount,
+            Resource::Bitwise => self.bitwise = amount,
+            Resource::EcOp => self.ec_op = amount,
+        }
+    }
+
+    /// Returns the resource with the highest weighted amount, which determines the fee,
+    /// along with that weighted amount.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights` - The weight of each resource.
+    pub fn limiting_factor(&self, weights: &Weights) -> (Resource, f32) {
+        let fee = self.extract_fee(weights);
+        Resource::ALL
+            .iter()
+            .map(|&resource| (resource, fee.get(resource)))
+            .max_by(|(_, x), (_, y)| x.total_cmp(y))
+            .unwrap_or((Resource::Steps, 0.0))
+    }
+
+    /// Override the amount of each resource provided.
+    ///
+    /// # Returns
+    ///
+    /// An error, leaving the resources unchanged, if any amount is negative or not finite.
     pub fn update(
         &mut self,
-        steps: &Option<String>,
-        pedersen: &Option<String>,
-        range_check: &Option<String>,
-        ecdsa: &Option<String>,
-        bitwise: &Option<String>,
-        ec_op: &Option<String>,
-    ) {
-        self.steps = steps
-            .as_ref()
-            .map(|x| x.parse().expect("Invalid steps number"))
-            .unwrap_or(self.steps);
-        self.pedersen = pedersen
-            .as_ref()
-            .map(|x| x.parse().expect("Invalid pedersen number"))
-            .unwrap_or(self.pedersen);
-        self.range_check = range_check
-            .as_ref()
-            .map(|x| x.parse().expect("Invalid range_check number"))
-            .unwrap_or(self.range_check);
-        self.ecdsa = ecdsa
-            .as_ref()
-            .map(|x| x.parse().expect("Invalid ecdsa number"))
-            .unwrap_or(self.ecdsa);
-        self.bitwise = bitwise
-            .as_ref()
-            .map(|x| x.parse().expect("Invalid bitwise number"))
-            .unwrap_or(self.bitwise);
-        self.ec_op = ec_op
-            .as_ref()
-            .map(|x| x.parse().expect("Invalid ec_op number"))
-            .unwrap_or(self.ec_op);
+        steps: &Option<f32>,
+        pedersen: &Option<f32>,
+        range_check: &Option<f32>,
+        ecdsa: &Option<f32>,
+        bitwise: &Option<f32>,
+        ec_op: &Option<f32>,
+    ) -> Result<()> {
+        let overrides = [
+            (Resource::Steps, steps),
+            (Resource::Pedersen, pedersen),
+            (Resource::RangeCheck, range_check),
+            (Resource::Ecdsa, ecdsa),
+            (Resource::Bitwise, bitwise),
+            (Resource::EcOp, ec_op),
+        ];
+        for (resource, amount) in overrides {
+            if let Some(amount) = amount {
+                validate_resource_count(resource, *amount)?;
+            }
+        }
+        for (resource, amount) in overrides {
+            if let Some(amount) = amount {
+                self.set(resource, *amount);
+            }
+        }
+        Ok(())
     }
 
     pub fn to_table(&self, weights: &Weights) -> String {
         let fee = self.extract_fee(weights);
+        self.table(
+            weights,
+            format!("Limiting factor: {}", fee.get_limiting_factor()),
+        )
+    }
+
+    /// Same as [`CairoResources::to_table`], followed by what the transaction actually paid.
+    pub fn to_table_with_fee(&self, weights: &Weights, fee_summary: &FeeSummary) -> Result<String> {
+        let limiting_factor = self.extract_fee(weights).get_limiting_factor();
+        let unit = fee_summary.fee_unit.name();
+        let mut actual_fee = format!(
+            "{} {unit} ({}",
+            fee_summary.actual_fee,
+            format_amount(
+                fee_summary.actual_fee,
+                fee_summary.fee_unit,
+                fee_summary.display_unit,
+                fee_summary.token_price
+            )?
+        );
+        if fee_summary.display_unit != Unit::Usd && fee_summary.token_price.is_some() {
+            actual_fee.push_str(&format!(
+                ", {}",
+                format_dollar_cost_at(
+                    fee_summary.actual_fee,
+                    fee_summary.fee_unit,
+                    fee_summary.token_price
+                )
+            ));
+        }
+        let mut footer = vec![
+            format!("Limiting factor: {limiting_factor}"),
+            format!("Actual fee: {actual_fee})"),
+        ];
+        if fee_summary.token_price.is_none() {
+            footer.push(String::from(
+                "USD cost: unavailable, pass --usd to query the fee token price",
+            ));
+        }
+        if let Some(gas_consumed) = fee_summary.gas_consumed {
+            footer.push(format!(
+                "Gas consumed: L1 gas: {} gas, L1 data gas: {} gas, L2 gas: {} gas",
+                gas_consumed.l1_gas, gas_consumed.l1_data_gas, gas_consumed.l2_gas
+            ));
+        }
+        match (fee_summary.gas_price, fee_summary.block_number) {
+            (Some(gas_price), Some(block_number)) => footer.push(format!(
+                "L1 gas price: {gas_price} {unit}/gas (block {block_number})"
+            )),
+            (Some(gas_price), None) => footer.push(format!("L1 gas price: {gas_price} {unit}/gas")),
+            (None, _) => footer.push(String::from(
+                "L1 gas price: unavailable, pass --block-file to derive the implied L1 gas",
+            )),
+        }
+        if let Some(l1_gas) = fee_summary.implied_l1_gas() {
+            let l1_gas = l1_gas.to_string().parse::<f32>()?;
+            footer.push(format!("Implied L1 gas: {l1_gas} gas"));
+            footer.push(format!(
+                "Gap to limiting factor: {} gas",
+                l1_gas - limiting_factor
+            ));
+        }
+        Ok(self.table(weights, footer.join("\n")))
+    }
+
+    fn table(&self, weights: &Weights, footer: String) -> String {
+        let fee = self.extract_fee(weights);
 
         let mut table = Table::new(vec![*self, *weights, fee]);
         table
-            .with(Panel::footer(format!(
-                "Limiting factor: {}",
-                fee.get_limiting_factor()
-            )))
+            .with(Panel::footer(footer))
             .with(Alignment::center())
             .with(Style::modern());
         table.to_string()
     }
 }
 
+/// Checks a resource count is a non-negative number.
+pub fn validate_resource_count(resource: Resource, amount: f32) -> Result<()> {
+    if !amount.is_finite() || amount < 0.0 {
+        return Err(eyre::eyre!(
+            "Invalid {} count {amount}, expected a non-negative number",
+            resource.name()
+        ));
+    }
+    Ok(())
+}
+
+/// What a transaction actually paid, summarized along its resources.
+#[derive(Debug, Clone, Copy)]
+pub struct FeeSummary {
+    /// The actual fee of the transaction.
+    pub actual_fee: U256,
+    /// The unit the actual fee is denominated in.
+    pub fee_unit: FeeUnit,
+    /// The block which included the transaction.
+    pub block_number: Option<u64>,
+    /// The L1 gas price of the block which included the transaction.
+    pub gas_price: Option<U256>,
+    /// The gas consumed, when the receipt reports it.
+    pub gas_consumed: Option<GasConsumed>,
+    /// The USD price of the fee token.
+    pub token_price: Option<f32>,
+    /// The unit the actual fee is displayed in.
+    pub display_unit: Unit,
+}
+
+impl FeeSummary {
+    /// Returns the L1 gas the transaction paid for.
+    /// Reported by the receipt when possible, derived from the actual fee and the gas price otherwise.
+    pub fn implied_l1_gas(&self) -> Option<U256> {
+        match (self.gas_consumed, self.gas_price) {
+            (Some(gas_consumed), _) => Some(gas_consumed.l1_gas),
+            (None, Some(gas_price)) => Some(
+                compute_static_tx_fee(self.actual_fee, gas_price)
+                    .ok()?
+                    .gas_units(),
+            ),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Summarize what a transaction actually paid.
+/// The gas price is read from the block file when provided,
+/// queried from the block which included the transaction when a gateway URL is provided.
+/// # Arguments
+/// * `tx_receipt` - The transaction receipt.
+/// * `source_network_gateway_url` - The network to query the inclusion block from, if any.
+/// * `block_file` - File containing the inclusion block.
+/// * `unit` - The unit to display the actual fee in.
+/// * `with_price` - Whether to query the USD price of the fee token.
+pub fn get_fee_summary(
+    tx_receipt: &TransactionReceipt,
+    source_network_gateway_url: Option<&str>,
+    block_file: Option<&str>,
+    unit: Option<Unit>,
+    with_price: bool,
+) -> Result<FeeSummary> {
+    let fee_unit = tx_receipt.fee_unit()?;
+    let block_number = tx_receipt.block_number()?;
+    let block = match (block_file, source_network_gateway_url, block_number) {
+        (Some(filename), _, _) => Some(Block::try_from_file(filename)?),
+        (None, Some(url), Some(block_number)) => {
+            match query_block(&block_number.to_string(), url) {
+                Ok(block) => Some(block),
+                Err(err) => {
+                    warn!("Could not query the inclusion block {block_number}: {err}");
+                    None
+                }
+            }
+        }
+        (None, _, _) => None,
+    };
+    let gas_price = match block {
+        Some(block) => Some(block.gas_prices()?.l1_gas.in_unit(fee_unit)),
+        None => None,
+    };
+    Ok(FeeSummary {
+        actual_fee: tx_receipt.actual_fee()?,
+        fee_unit,
+        block_number,
+        gas_price,
+        gas_consumed: tx_receipt.gas_consumed()?,
+        token_price: if with_price {
+            get_token_price(fee_unit)
+        } else {
+            None
+        },
+        display_unit: unit.unwrap_or(Unit::token(fee_unit)),
+    })
+}
+
 pub fn get_resources_used(
     tx_hash: Option<&str>,
     source_network_gateway_url: Option<&str>,
     transaction_file: Option<&str>,
 ) -> Result<CairoResources> {
-    let tx_receipt = match (tx_hash, source_network_gateway_url, transaction_file) {
-        (_, _, Some(filename)) => TransactionReceipt::try_from_file(filename)?,
-        (Some(hash), Some(url), None) => query_tx_receipt(hash, url)?,
-        (_, _, _) => {
-            return Err(eyre::eyre!(
-                "Provide either a filename or a transaction_hash and source network gateway url"
-            ));
-        }
-    };
+    get_transaction_receipt(tx_hash, source_network_gateway_url, transaction_file)?.resources_used()
+}
 
-    tx_receipt.resources_used()
+/// Read a transaction receipt from a file, or query it from a network.
+pub fn get_transaction_receipt(
+    tx_hash: Option<&str>,
+    source_network_gateway_url: Option<&str>,
+    transaction_file: Option<&str>,
+) -> Result<TransactionReceipt> {
+    match (tx_hash, source_network_gateway_url, transaction_file) {
+        (_, _, Some(filename)) => TransactionReceipt::try_from_file(filename),
+        (Some(hash), Some(url), None) => query_tx_receipt(hash, url),
+        (_, _, _) => Err(eyre::eyre!(
+            "Provide either a filename or a transaction_hash and source network gateway url"
+        )),
+    }
 }
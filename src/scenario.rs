@@ -0,0 +1,245 @@
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use ethers::types::U256;
+use eyre::Result;
+use tabled::{Alignment, Style, Table, Tabled};
+
+use crate::resources::{CairoResources, FeeSummary, Resource, Weights};
+use crate::units::format_amount;
+
+/// The name of the scenario made of the `--scale` flags.
+pub const DEFAULT_SCENARIO_NAME: &str = "scaled";
+
+/// A relative change of one resource, such as `pedersen=0.6`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResourceScale {
+    pub resource: Resource,
+    pub factor: f32,
+}
+
+impl FromStr for ResourceScale {
+    type Err = String;
+
+    /// Parse a resource scale, written `<resource>=<factor>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (resource, factor) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid scale `{s}`, expected <resource>=<factor>"))?;
+        let resource = Resource::from_str(resource.trim(), true)
+            .map_err(|_| format!("Unknown resource `{}`", resource.trim()))?;
+        let factor: f32 = factor
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid scale factor `{}`", factor.trim()))?;
+        if !factor.is_finite() || factor < 0.0 {
+            return Err(format!(
+                "Invalid scale factor `{factor}`, expected a non-negative number"
+            ));
+        }
+        Ok(Self { resource, factor })
+    }
+}
+
+/// A named set of resource scales, answering "what if the transaction used less of these?".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub name: String,
+    pub scales: Vec<ResourceScale>,
+}
+
+impl Scenario {
+    pub fn new(name: &str, scales: Vec<ResourceScale>) -> Self {
+        Self {
+            name: name.to_string(),
+            scales,
+        }
+    }
+
+    /// Apply the scenario to the resources used.
+    ///
+    /// # Arguments
+    ///
+    /// * `resources` - The resources used by the transaction.
+    ///
+    /// # Returns
+    ///
+    /// The resources the transaction would use in this scenario.
+    pub fn apply(&self, resources: &CairoResources) -> CairoResources {
+        let mut scaled = *resources;
+        for scale in &self.scales {
+            scaled.set(scale.resource, scaled.get(scale.resource) * scale.factor);
+        }
+        scaled
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = String;
+
+    /// Parse a scenario, written `<name>:<resource>=<factor>,<resource>=<factor>`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use howmuch_rs::scenario::Scenario;
+    ///
+    /// let scenario: Scenario = "cheaper-hash:pedersen=0.6,steps=0.9".parse().unwrap();
+    /// assert_eq!(scenario.name, "cheaper-hash");
+    /// assert_eq!(scenario.scales.len(), 2);
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, scales) = s.split_once(':').ok_or_else(|| {
+            format!("Invalid scenario `{s}`, expected <name>:<resource>=<factor>,...")
+        })?;
+        if name.trim().is_empty() {
+            return Err(format!("Invalid scenario `{s}`, missing its name"));
+        }
+        let scales = scales
+            .split(',')
+            .map(ResourceScale::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(name.trim(), scales))
+    }
+}
+
+#[derive(Tabled)]
+struct ScenarioRow {
+    scenario: String,
+    #[tabled(rename = "limiting resource")]
+    limiting_resource: &'static str,
+    #[tabled(rename = "limiting factor")]
+    limiting_factor: f32,
+    change: String,
+    #[tabled(rename = "estimated fee")]
+    estimated_fee: String,
+}
+
+/// Returns the fee a transaction would pay with another limiting factor.
+/// Only the computation part of the actual fee changes, by the difference of the limiting factors
+/// in L1 gas, so that the unchanged transaction costs its actual fee.
+fn scenario_fee(fee_summary: &FeeSummary, baseline: f32, limiting_factor: f32) -> Option<U256> {
+    let delta = (f64::from(limiting_factor) - f64::from(baseline)).round();
+    if delta == 0.0 {
+        return Some(fee_summary.actual_fee);
+    }
+    let delta_fee = U256::from(delta.abs() as u128).checked_mul(fee_summary.gas_price?)?;
+    if delta > 0.0 {
+        fee_summary.actual_fee.checked_add(delta_fee)
+    } else {
+        Some(fee_summary.actual_fee.saturating_sub(delta_fee))
+    }
+}
+
+/// Compare the fee of a transaction before and after each scenario.
+/// The fee of each scenario starts from the actual fee, see [`scenario_fee`].
+///
+/// # Arguments
+///
+/// * `resources` - The resources used by the transaction.
+/// * `weights` - The weight of each resource.
+/// * `scenarios` - The scenarios to compare.
+/// * `fee_summary` - What the transaction actually paid, used to price the limiting factors.
+///
+/// # Returns
+///
+/// A table with one row for the transaction as is, then one row per scenario.
+pub fn compare_scenarios(
+    resources: &CairoResources,
+    weights: &Weights,
+    scenarios: &[Scenario],
+    fee_summary: Option<&FeeSummary>,
+) -> Result<String> {
+    let (_, baseline) = resources.limiting_factor(weights);
+    let row = |name: &str, resources: &CairoResources| -> Result<ScenarioRow> {
+        let (limiting_resource, limiting_factor) = resources.limiting_factor(weights);
+        let change = if baseline == 0.0 {
+            String::from("-")
+        } else {
+            format!("{:+.1}%", (limiting_factor / baseline - 1.0) * 100.0)
+        };
+        let fee = fee_summary.and_then(|fee_summary| {
+            scenario_fee(fee_summary, baseline, limiting_factor).map(|fee| (fee_summary, fee))
+        });
+        let estimated_fee = match fee {
+            Some((fee_summary, fee)) => format_amount(
+                fee,
+                fee_summary.fee_unit,
+                fee_summary.display_unit,
+                fee_summary.token_price,
+            )?,
+            None => String::from("-"),
+        };
+        Ok(ScenarioRow {
+            scenario: name.to_string(),
+            limiting_resource: limiting_resource.name(),
+            limiting_factor,
+            change,
+            estimated_fee,
+        })
+    };
+
+    let mut rows = vec![row("actual", resources)?];
+    for scenario in scenarios {
+        rows.push(row(&scenario.name, &scenario.apply(resources))?);
+    }
+
+    let mut table = Table::new(rows);
+    table.with(Alignment::center()).with(Style::modern());
+    Ok(table.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Block, FeeUnit, TransactionReceipt};
+    use crate::units::Unit;
+
+    fn example() -> (CairoResources, FeeSummary) {
+        let receipt = TransactionReceipt::try_from_file("tests/example.json").unwrap();
+        let block = Block::try_from_file("tests/example_block.json").unwrap();
+        let fee_summary = FeeSummary {
+            actual_fee: receipt.actual_fee().unwrap(),
+            fee_unit: FeeUnit::Wei,
+            block_number: receipt.block_number().unwrap(),
+            gas_price: Some(block.gas_price().unwrap()),
+            gas_consumed: None,
+            token_price: None,
+            display_unit: Unit::Eth,
+        };
+        (receipt.resources_used().unwrap(), fee_summary)
+    }
+
+    fn weights() -> Weights {
+        Weights::new("weight", 0.05, 1.6, 0.8, 102.4, 3.2, 51.2)
+    }
+
+    #[test]
+    fn unchanged_scenario_costs_the_actual_fee() {
+        let (resources, fee_summary) = example();
+        let (_, baseline) = resources.limiting_factor(&weights());
+        assert_eq!(
+            scenario_fee(&fee_summary, baseline, baseline),
+            Some(U256::from(207715885355485u64))
+        );
+        let unchanged = Scenario::new("unchanged", vec![]);
+        let table =
+            compare_scenarios(&resources, &weights(), &[unchanged], Some(&fee_summary)).unwrap();
+        assert_eq!(table.matches("0.000207715885355485 ETH").count(), 2);
+    }
+
+    #[test]
+    fn scenarios_change_the_computation_part_of_the_fee() {
+        let (resources, fee_summary) = example();
+        let gas_price = fee_summary.gas_price.unwrap();
+        let (_, baseline) = resources.limiting_factor(&weights());
+        assert_eq!(
+            scenario_fee(&fee_summary, baseline, baseline + 10.0),
+            Some(fee_summary.actual_fee + gas_price * 10)
+        );
+        assert_eq!(
+            scenario_fee(&fee_summary, baseline, baseline - 10.0),
+            Some(fee_summary.actual_fee - gas_price * 10)
+        );
+    }
+}
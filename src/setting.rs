@@ -0,0 +1,25 @@
+use std::sync::RwLock;
+
+/// A process wide setting, such as a command line option the library functions follow.
+/// It is set once, before any query, and read by every query.
+pub(crate) struct Setting<T>(RwLock<T>);
+
+impl<T: Clone> Setting<T> {
+    pub(crate) const fn new(default: T) -> Self {
+        Self(RwLock::new(default))
+    }
+
+    pub(crate) fn set(&self, value: T) {
+        match self.0.write() {
+            Ok(mut setting) => *setting = value,
+            Err(poisoned) => *poisoned.into_inner() = value,
+        }
+    }
+
+    pub(crate) fn get(&self) -> T {
+        match self.0.read() {
+            Ok(setting) => setting.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+}
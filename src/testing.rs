@@ -0,0 +1,282 @@
+//! An in-process mock of the feeder gateway and of the price oracle,
+//! for integration tests which must not hit the network.
+//!
+//! ```rust
+//! use howmuch_rs::{currencies, estimate_cost_on_network, testing::MockGateway};
+//!
+//! let source = MockGateway::new()
+//!     .with_receipt_file("tests/example.json")
+//!     .unwrap()
+//!     .with_block_file("tests/example_block.json")
+//!     .unwrap()
+//!     .start()
+//!     .unwrap();
+//! let destination = MockGateway::new()
+//!     .with_gas_price(15925, 10_000_000_000u64.into())
+//!     .with_token_price("ethereum", 2000.0)
+//!     .start()
+//!     .unwrap();
+//! currencies::set_price_oracle_url(&destination.price_oracle_url());
+//! let fees = estimate_cost_on_network(
+//!     "0x2d98bda2ac11676c8bbf75d7245de850a7ba634a894ea322656a62ffc64b858",
+//!     &source.gateway_url(),
+//!     &destination.gateway_url(),
+//!     &None,
+//!     &None,
+//!     &Some(true),
+//! )
+//! .unwrap();
+//! assert_eq!(fees, "0.000339950000000000 ETH ($0.6799 USD)");
+//! ```
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use ethers::types::U256;
+use eyre::Result;
+
+use crate::model::{GasPrices, ResourcePrice};
+
+/// Path the mock feeder gateway is served at.
+pub const GATEWAY_PATH: &str = "/feeder_gateway";
+
+/// Path the mock price oracle is served at.
+pub const PRICE_ORACLE_PATH: &str = "/price_oracle";
+
+/// Configuration of a mock feeder gateway and price oracle.
+#[derive(Debug, Default, Clone)]
+pub struct MockGateway {
+    responses: HashMap<String, String>,
+    latest_block_number: Option<u64>,
+    token_prices: HashMap<String, f32>,
+}
+
+impl MockGateway {
+    /// Creates a mock which serves nothing yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves `body` for a GET request to `{gateway_url}/{endpoint}`, query included.
+    pub fn with_response(mut self, endpoint: &str, body: &str) -> Self {
+        self.responses.insert(
+            format!("{GATEWAY_PATH}/{}", endpoint.trim_start_matches('/')),
+            body.to_string(),
+        );
+        self
+    }
+
+    /// Serves a transaction receipt, for its `transaction_hash`.
+    pub fn with_receipt(self, receipt: &str) -> Result<Self> {
+        let j = json::parse(receipt)?;
+        let tx_hash = j["transaction_hash"]
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("Missing transaction hash in receipt"))?;
+        Ok(self.with_response(
+            &format!("get_transaction_receipt?transactionHash={tx_hash}"),
+            receipt,
+        ))
+    }
+
+    /// Serves the transaction receipt contained in a file.
+    pub fn with_receipt_file(self, filename: &str) -> Result<Self> {
+        self.with_receipt(&std::fs::read_to_string(filename)?)
+    }
+
+    /// Serves a transaction, for its `transaction_hash`.
+    pub fn with_transaction(self, transaction: &str) -> Result<Self> {
+        let j = json::parse(transaction)?;
+        let tx_hash = j["transaction_hash"]
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("Missing transaction hash in transaction"))?;
+        Ok(self.with_response(
+            &format!("get_transaction?transactionHash={tx_hash}"),
+            transaction,
+        ))
+    }
+
+    /// Serves a transaction trace, which doesn't carry its transaction hash.
+    pub fn with_trace(self, tx_hash: &str, trace: &str) -> Self {
+        self.with_response(
+            &format!("get_transaction_trace?transactionHash={tx_hash}"),
+            trace,
+        )
+    }
+
+    /// Serves a block, for its `block_number`.
+    /// The block with the highest number is also served as the latest block.
+    pub fn with_block(mut self, block: &str) -> Result<Self> {
+        let j = json::parse(block)?;
+        let block_number = j["block_number"]
+            .as_u64()
+            .ok_or_else(|| eyre::eyre!("Missing block number in block"))?;
+        self.latest_block_number = self.latest_block_number.max(Some(block_number));
+        Ok(self.with_response(&format!("get_block?blockNumber={block_number}"), block))
+    }
+
+    /// Serves the block contained in a file.
+    pub fn with_block_file(self, filename: &str) -> Result<Self> {
+        self.with_block(&std::fs::read_to_string(filename)?)
+    }
+
+    /// Serves an empty block with an L1 gas price, in both wei and fri.
+    pub fn with_gas_price(self, block_number: u64, gas_price: U256) -> Self {
+        let price = ResourcePrice {
+            price_in_wei: gas_price,
+            price_in_fri: gas_price,
+        };
+        self.with_gas_prices(
+            block_number,
+            GasPrices {
+                l1_gas: price,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Serves an empty block with the given gas prices.
+    /// Blocks are 30 seconds apart.
+    pub fn with_gas_prices(self, block_number: u64, gas_prices: GasPrices) -> Self {
+        let block = serde_json::json!({
+            "block_hash": format!("{block_number:#x}"),
+            "block_number": block_number,
+            "status": "ACCEPTED_ON_L2",
+            "timestamp": 1_700_000_000 + 30 * block_number,
+            "gas_price": gas_prices.l1_gas.price_in_wei,
+            "l1_gas_price": gas_prices.l1_gas,
+            "l1_data_gas_price": gas_prices.l1_data_gas,
+            "l2_gas_price": gas_prices.l2_gas,
+            "transactions": [],
+            "transaction_receipts": [],
+        });
+        self.with_block(&block.to_string())
+            .expect("generated block has a block number")
+    }
+
+    /// Serves the USD price of a token, by its coingecko id (`ethereum`, `starknet`).
+    pub fn with_token_price(mut self, id: &str, usd: f32) -> Self {
+        self.token_prices.insert(id.to_string(), usd);
+        self
+    }
+
+    /// Starts serving on a random local port, until the returned server is dropped.
+    pub fn start(mut self) -> Result<MockServer> {
+        if let Some(block_number) = self.latest_block_number {
+            if let Some(block) = self
+                .responses
+                .get(&format!(
+                    "{GATEWAY_PATH}/get_block?blockNumber={block_number}"
+                ))
+                .cloned()
+            {
+                self = self.with_response("get_block?blockNumber=latest", &block);
+            }
+        }
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = format!("http://{}", listener.local_addr()?);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stopped = stopped.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        // A failing client must not take the server down.
+                        let _ = self.serve(stream);
+                    }
+                }
+            })
+        };
+        Ok(MockServer {
+            address,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    fn serve(&self, mut stream: TcpStream) -> Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Drain the headers, requests have no body.
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+        let target = request_line.split_whitespace().nth(1).unwrap_or_default();
+        let (status, body) = match self.respond(target) {
+            Some(body) => ("200 OK", body),
+            None => (
+                "404 Not Found",
+                serde_json::json!({
+                    "code": "StarknetErrorCode.NOT_FOUND",
+                    "message": format!("No mock response for {target}"),
+                })
+                .to_string(),
+            ),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )?;
+        Ok(stream.flush()?)
+    }
+
+    fn respond(&self, target: &str) -> Option<String> {
+        if target.starts_with(&format!("{PRICE_ORACLE_PATH}/simple/price")) {
+            let prices: serde_json::Map<String, serde_json::Value> = self
+                .token_prices
+                .iter()
+                .map(|(id, usd)| (id.clone(), serde_json::json!({ "usd": usd })))
+                .collect();
+            return Some(serde_json::Value::Object(prices).to_string());
+        }
+        // Historical prices are the same as the current ones.
+        if let Some(coin) = target.strip_prefix(&format!("{PRICE_ORACLE_PATH}/coins/")) {
+            let id = coin.split('/').next().unwrap_or_default();
+            let usd = self.token_prices.get(id)?;
+            return Some(
+                serde_json::json!({ "id": id, "market_data": { "current_price": { "usd": usd } } })
+                    .to_string(),
+            );
+        }
+        self.responses.get(target).cloned()
+    }
+}
+
+/// A running mock feeder gateway and price oracle.
+#[derive(Debug)]
+pub struct MockServer {
+    address: String,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Returns the mock feeder gateway URL.
+    pub fn gateway_url(&self) -> String {
+        format!("{}{GATEWAY_PATH}", self.address)
+    }
+
+    /// Returns the mock price oracle URL, see [`crate::currencies::set_price_oracle_url`].
+    pub fn price_oracle_url(&self) -> String {
+        format!("{}{PRICE_ORACLE_PATH}", self.address)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake the server up so that it notices it is stopped.
+        let _ = TcpStream::connect(self.address.trim_start_matches("http://"));
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
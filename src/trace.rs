@@ -0,0 +1,464 @@
+use ethers::types::U256;
+use eyre::Result;
+use json::JsonValue;
+
+use crate::abi::SelectorNames;
+use crate::currencies::get_token_price;
+use crate::model::{parse_execution_resources, FeeUnit, TransactionReceipt, TransactionTrace};
+use crate::query_tx_trace;
+use crate::resources::{CairoResources, Resource, Weights};
+use crate::units::{format_amount, Unit};
+
+/// The phases of a transaction, each run as its own invocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallPhase {
+    Validate,
+    Execute,
+    FeeTransfer,
+}
+
+impl CallPhase {
+    pub const ALL: [CallPhase; 3] = [
+        CallPhase::Validate,
+        CallPhase::Execute,
+        CallPhase::FeeTransfer,
+    ];
+
+    /// Returns the phase name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CallPhase::Validate => "validate",
+            CallPhase::Execute => "execute",
+            CallPhase::FeeTransfer => "fee_transfer",
+        }
+    }
+
+    /// Returns the trace keys holding the phase invocation, feeder gateway first.
+    fn trace_keys(&self) -> &'static [&'static str] {
+        match self {
+            CallPhase::Validate => &["validate_invocation"],
+            // Account deployments run their constructor instead.
+            CallPhase::Execute => &[
+                "function_invocation",
+                "execute_invocation",
+                "constructor_invocation",
+            ],
+            CallPhase::FeeTransfer => &["fee_transfer_invocation"],
+        }
+    }
+}
+
+/// Output format of a call tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TraceFormat {
+    /// An indented tree, with the resources and fee of each call.
+    Tree,
+    /// Folded stacks, as read by inferno and flamegraph.pl.
+    Folded,
+}
+
+/// What the width of a frame represents in a flamegraph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FlameWeight {
+    /// The Cairo steps run by the call.
+    Steps,
+    /// The gas the call is responsible for, through the resource limiting the transaction.
+    Fee,
+}
+
+/// A contract call, along with the calls it made.
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub contract_address: String,
+    pub selector: String,
+    pub entry_point_type: Option<String>,
+    pub call_type: Option<String>,
+    /// The class of the called contract, when the trace reports it.
+    pub class_hash: Option<String>,
+    /// The name of the entrypoint, once named with [`CallTree::name_calls`].
+    pub entry_point_name: Option<String>,
+    /// The resources used by the call, including its internal calls.
+    pub resources: CairoResources,
+    pub internal_calls: Vec<Call>,
+}
+
+impl Call {
+    fn from_json(j: &JsonValue) -> Result<Self> {
+        let contract_address = j["contract_address"]
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("Missing contract address in call"))?;
+        let selector = j["selector"]
+            .as_str()
+            .or_else(|| j["entry_point_selector"].as_str())
+            .unwrap_or_default();
+        let internal_calls = if j.has_key("internal_calls") {
+            &j["internal_calls"]
+        } else {
+            &j["calls"]
+        }
+        .members()
+        .map(Call::from_json)
+        .collect::<Result<Vec<_>>>()?;
+        let execution_resources = &j["execution_resources"];
+        if execution_resources.has_key("l2_gas") && !execution_resources.has_key("steps") {
+            return Err(eyre::eyre!(
+                "The trace reports the gas of each call rather than its steps and builtins, \
+                 which is not supported: use a feeder gateway trace or an RPC 0.7 trace"
+            ));
+        }
+        Ok(Self {
+            contract_address: contract_address.to_string(),
+            selector: selector.to_string(),
+            entry_point_type: j["entry_point_type"].as_str().map(String::from),
+            call_type: j["call_type"].as_str().map(String::from),
+            class_hash: j["class_hash"].as_str().map(String::from),
+            entry_point_name: None,
+            resources: parse_execution_resources(execution_resources, "call"),
+            internal_calls,
+        })
+    }
+
+    /// Returns the called entrypoint: its name when known, its selector otherwise.
+    pub fn entry_point(&self) -> &str {
+        self.entry_point_name.as_deref().unwrap_or(&self.selector)
+    }
+
+    fn name_calls(&mut self, names: &SelectorNames) {
+        self.entry_point_name = names.name(&self.selector).map(String::from);
+        for call in &mut self.internal_calls {
+            call.name_calls(names);
+        }
+    }
+
+    fn contracts<'a>(&'a self, contracts: &mut Vec<(&'a str, Option<&'a str>)>) {
+        if !contracts
+            .iter()
+            .any(|(address, _)| *address == self.contract_address)
+        {
+            contracts.push((&self.contract_address, self.class_hash.as_deref()));
+        }
+        for call in &self.internal_calls {
+            call.contracts(contracts);
+        }
+    }
+
+    /// Returns the resources used by the call itself, excluding its internal calls.
+    pub fn own_resources(&self) -> CairoResources {
+        let mut own = self.resources;
+        own.category = "own";
+        for call in &self.internal_calls {
+            for resource in Resource::ALL {
+                own.set(resource, own.get(resource) - call.resources.get(resource));
+            }
+        }
+        // Traces may round resources, an internal call can't make a caller use less than nothing.
+        for resource in Resource::ALL {
+            own.set(resource, own.get(resource).max(0.0));
+        }
+        own
+    }
+}
+
+/// The calls of a transaction, per phase.
+#[derive(Debug, Clone)]
+pub struct CallTree {
+    pub invocations: Vec<(CallPhase, Call)>,
+}
+
+/// The actual fee of a transaction, to attribute to its calls.
+#[derive(Debug, Clone, Copy)]
+pub struct FeeAttribution {
+    pub actual_fee: U256,
+    pub fee_unit: FeeUnit,
+    pub display_unit: Unit,
+    pub token_price: Option<f32>,
+}
+
+impl FeeAttribution {
+    /// Attribute the actual fee of a transaction receipt.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx_receipt` - The transaction receipt.
+    /// * `unit` - The unit to display fees in, if not the token paying the fee.
+    pub fn from_receipt(tx_receipt: &TransactionReceipt, unit: Option<Unit>) -> Result<Self> {
+        let fee_unit = tx_receipt.fee_unit()?;
+        let display_unit = unit.unwrap_or(Unit::token(fee_unit));
+        let token_price = match display_unit {
+            Unit::Usd => get_token_price(fee_unit),
+            _ => None,
+        };
+        Ok(Self {
+            actual_fee: tx_receipt.actual_fee()?,
+            fee_unit,
+            display_unit,
+            token_price,
+        })
+    }
+}
+
+impl CallTree {
+    /// Build the call tree of a transaction trace.
+    /// Both the feeder gateway and the RPC invocation names are supported,
+    /// phases missing from the trace (such as the validation of legacy transactions) are skipped.
+    pub fn try_from_trace(trace: &TransactionTrace) -> Result<Self> {
+        let j = json::parse(&trace.0)?;
+        let mut invocations = vec![];
+        for phase in CallPhase::ALL {
+            let invocation = phase
+                .trace_keys()
+                .iter()
+                .map(|key| &j[*key])
+                // A reverted RPC execution only holds its revert reason.
+                .find(|invocation| invocation.has_key("contract_address"));
+            if let Some(invocation) = invocation {
+                invocations.push((phase, Call::from_json(invocation)?));
+            }
+        }
+        if invocations.is_empty() {
+            return Err(eyre::eyre!("No invocation found in the transaction trace"));
+        }
+        Ok(Self { invocations })
+    }
+
+    /// Name the entrypoint of every call, from the names of their selectors.
+    pub fn name_calls(&mut self, names: &SelectorNames) {
+        for (_, call) in &mut self.invocations {
+            call.name_calls(names);
+        }
+    }
+
+    /// Returns the address of every called contract, along with its class hash when the trace reports it.
+    pub fn contracts(&self) -> Vec<(&str, Option<&str>)> {
+        let mut contracts = vec![];
+        for (_, call) in &self.invocations {
+            call.contracts(&mut contracts);
+        }
+        contracts
+    }
+
+    /// Returns the invocation of a phase, if the transaction has one.
+    pub fn invocation(&self, phase: CallPhase) -> Option<&Call> {
+        self.invocations
+            .iter()
+            .find(|(p, _)| *p == phase)
+            .map(|(_, call)| call)
+    }
+
+    /// Returns the resources used by all the invocations.
+    pub fn total_resources(&self) -> CairoResources {
+        let mut total = CairoResources::new("total", 0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        for (_, call) in &self.invocations {
+            for resource in Resource::ALL {
+                total.set(resource, total.get(resource) + call.resources.get(resource));
+            }
+        }
+        total
+    }
+
+    /// Returns the share of the fee a call is responsible for.
+    /// The fee is attributed pro rata of the resource limiting the whole transaction,
+    /// so that the shares of the invocations add up to the actual fee.
+    ///
+    /// # Arguments
+    ///
+    /// * `resources` - The resources used by the call.
+    /// * `weights` - The weight of each resource.
+    pub fn fee_share(&self, resources: &CairoResources, weights: &Weights) -> f32 {
+        let total = self.total_resources();
+        let (limiting_resource, _) = total.limiting_factor(weights);
+        match total.get(limiting_resource) {
+            amount if amount > 0.0 => resources.get(limiting_resource) / amount,
+            _ => 0.0,
+        }
+    }
+
+    /// Same as [`CallTree::fee_share`], applied to the actual fee.
+    pub fn attribute_fee(
+        &self,
+        resources: &CairoResources,
+        weights: &Weights,
+        actual_fee: U256,
+    ) -> U256 {
+        let total = self.total_resources();
+        let (limiting_resource, _) = total.limiting_factor(weights);
+        match total.get(limiting_resource).round() as u128 {
+            0 => U256::zero(),
+            amount => {
+                actual_fee
+                    .saturating_mul(U256::from(resources.get(limiting_resource).round() as u128))
+                    / amount
+            }
+        }
+    }
+
+    /// Export the call tree as folded stacks, one line per call: its stack of frames and its own weight.
+    /// The stacks start with the phase, followed by a `<contract address>::<entrypoint>` frame per call.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights` - The weight of each resource.
+    /// * `flame_weight` - What the weight of each call represents.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use howmuch_rs::model::TransactionTrace;
+    /// use howmuch_rs::resources::Weights;
+    /// use howmuch_rs::trace::{CallTree, FlameWeight};
+    ///
+    /// let trace = TransactionTrace(String::from(r#"{"function_invocation": {
+    ///     "contract_address": "0x1", "selector": "0x2",
+    ///     "execution_resources": {"n_steps": 100, "builtin_instance_counter": {}},
+    ///     "internal_calls": [{
+    ///         "contract_address": "0x3", "selector": "0x4",
+    ///         "execution_resources": {"n_steps": 40, "builtin_instance_counter": {}},
+    ///         "internal_calls": []
+    ///     }]
+    /// }}"#));
+    /// let weights = Weights::new("weight", 0.05, 1.6, 0.8, 102.4, 3.2, 51.2);
+    /// let folded = CallTree::try_from_trace(&trace).unwrap().to_folded(&weights, FlameWeight::Steps);
+    /// assert_eq!(folded, "execute;0x1::0x2 60\nexecute;0x1::0x2;0x3::0x4 40");
+    /// ```
+    pub fn to_folded(&self, weights: &Weights, flame_weight: FlameWeight) -> String {
+        let (limiting_resource, _) = self.total_resources().limiting_factor(weights);
+        let weigh = |call: &Call| {
+            let own = call.own_resources();
+            match flame_weight {
+                FlameWeight::Steps => own.steps,
+                FlameWeight::Fee => own.get(limiting_resource) * weights.get(limiting_resource),
+            }
+        };
+
+        let mut lines = vec![];
+        let mut stack = vec![];
+        for (phase, call) in &self.invocations {
+            stack.push((vec![phase.name().to_string()], call));
+            while let Some((mut frames, call)) = stack.pop() {
+                frames.push(format!("{}::{}", call.contract_address, call.entry_point()));
+                let weight = weigh(call).round() as u64;
+                // Flamegraphs drop the zero weight lines anyway.
+                if weight > 0 {
+                    lines.push(format!("{} {weight}", frames.join(";")));
+                }
+                for internal_call in call.internal_calls.iter().rev() {
+                    stack.push((frames.clone(), internal_call));
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Render the call tree, with the resources used by each call and, when known, its share of the fee.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights` - The weight of each resource.
+    /// * `fee` - The actual fee of the transaction.
+    pub fn to_tree(&self, weights: &Weights, fee: Option<&FeeAttribution>) -> Result<String> {
+        let mut renderer = TreeRenderer {
+            tree: self,
+            weights,
+            fee,
+            lines: vec![],
+        };
+        for (phase, call) in &self.invocations {
+            renderer.render(call, phase.name(), "", "")?;
+        }
+        Ok(renderer.lines.join("\n"))
+    }
+}
+
+struct TreeRenderer<'a> {
+    tree: &'a CallTree,
+    weights: &'a Weights,
+    fee: Option<&'a FeeAttribution>,
+    lines: Vec<String>,
+}
+
+impl TreeRenderer<'_> {
+    fn render(&mut self, call: &Call, label: &str, prefix: &str, child_prefix: &str) -> Result<()> {
+        let mut line = format!(
+            "{prefix}{label} {}::{} [{}]",
+            call.contract_address,
+            call.entry_point(),
+            format_resources(&call.resources)
+        );
+        if let Some(fee) = self.fee {
+            let amount = self
+                .tree
+                .attribute_fee(&call.resources, self.weights, fee.actual_fee);
+            line.push_str(&format!(
+                " {} ({:.1}%)",
+                format_amount(amount, fee.fee_unit, fee.display_unit, fee.token_price)?,
+                self.tree.fee_share(&call.resources, self.weights) * 100.0
+            ));
+        }
+        self.lines.push(line);
+
+        for (i, internal_call) in call.internal_calls.iter().enumerate() {
+            let (branch, indent) = if i + 1 == call.internal_calls.len() {
+                ("└─ ", "   ")
+            } else {
+                ("├─ ", "│  ")
+            };
+
+            self.render(
+                internal_call,
+                "call",
+                &format!("{child_prefix}{branch}"),
+                &format!("{child_prefix}{indent}"),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Read a transaction trace from a file, or query it from a network, and build its call tree.
+pub fn get_call_tree(
+    tx_hash: Option<&str>,
+    network_gateway_url: &str,
+    trace_file: Option<&str>,
+) -> Result<CallTree> {
+    let trace = match (tx_hash, trace_file) {
+        (_, Some(filename)) => TransactionTrace::try_from_file(filename)?,
+        (Some(hash), None) => query_tx_trace(hash, network_gateway_url)?,
+        (None, None) => {
+            return Err(eyre::eyre!(
+                "Provide either a trace file or a transaction hash"
+            ))
+        }
+    };
+    CallTree::try_from_trace(&trace)
+}
+
+/// Format the non-zero resources, steps first.
+fn format_resources(resources: &CairoResources) -> String {
+    Resource::ALL
+        .iter()
+        .filter(|&&resource| resource == Resource::Steps || resources.get(resource) > 0.0)
+        .map(|&resource| format!("{}: {}", resource.name(), resources.get(resource)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deploy_account_trace_runs_the_constructor() {
+        let trace = TransactionTrace::try_from_file("tests/deploy_account_trace.json").unwrap();
+        let call_tree = CallTree::try_from_trace(&trace).unwrap();
+        let phases: Vec<CallPhase> = call_tree
+            .invocations
+            .iter()
+            .map(|(phase, _)| *phase)
+            .collect();
+        assert_eq!(phases, CallPhase::ALL);
+        let constructor = call_tree.invocation(CallPhase::Execute).unwrap();
+        assert_eq!(constructor.entry_point_type.as_deref(), Some("CONSTRUCTOR"));
+        assert_eq!(constructor.resources.steps, 388.0);
+        assert_eq!(call_tree.total_resources().steps, 3490.0);
+    }
+}
@@ -0,0 +1,136 @@
+use ethers::{types::U256, utils};
+use eyre::Result;
+
+use crate::currencies::{format_dollar_cost_at, get_token_price};
+use crate::model::FeeUnit;
+
+/// A unit fee amounts can be displayed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Unit {
+    Wei,
+    Gwei,
+    Eth,
+    Fri,
+    Strk,
+    Usd,
+}
+
+impl Unit {
+    /// Returns the unit amounts paid in `fee_unit` are displayed in by default, the token itself.
+    pub fn token(fee_unit: FeeUnit) -> Self {
+        match fee_unit {
+            FeeUnit::Wei => Unit::Eth,
+            FeeUnit::Fri => Unit::Strk,
+        }
+    }
+
+    /// Returns the fee unit this unit is a multiple of, `None` for fiat units.
+    pub fn fee_unit(&self) -> Option<FeeUnit> {
+        match self {
+            Unit::Wei | Unit::Gwei | Unit::Eth => Some(FeeUnit::Wei),
+            Unit::Fri | Unit::Strk => Some(FeeUnit::Fri),
+            Unit::Usd => None,
+        }
+    }
+
+    /// Returns the number of decimals of the unit, relative to its fee unit.
+    pub fn decimals(&self) -> u32 {
+        match self {
+            Unit::Wei | Unit::Fri | Unit::Usd => 0,
+            Unit::Gwei => 9,
+            Unit::Eth | Unit::Strk => 18,
+        }
+    }
+
+    /// Returns the unit symbol.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Wei => "wei",
+            Unit::Gwei => "gwei",
+            Unit::Eth => "ETH",
+            Unit::Fri => "fri",
+            Unit::Strk => "STRK",
+            Unit::Usd => "USD",
+        }
+    }
+}
+
+/// Formats an amount denominated in `from` in the `to` unit.
+/// # Arguments
+/// * `amount` - The amount, in wei or fri.
+/// * `from` - The unit the amount is denominated in.
+/// * `to` - The unit to display the amount in.
+/// * `token_price` - The USD price of the token, only needed to display USD.
+/// # Returns
+/// The formatted amount, followed by its unit symbol.
+/// # Errors
+/// If the amount can't be converted, such as wei to STRK, or USD without a token price.
+/// # Example
+/// ```
+/// use howmuch_rs::{model::FeeUnit, units::{format_amount, Unit}};
+/// use ethers::types::U256;
+/// let fee = U256::from(207715885355485u64);
+/// assert_eq!(format_amount(fee, FeeUnit::Wei, Unit::Gwei, None).unwrap(), "207715.885355485 gwei");
+/// assert_eq!(format_amount(fee, FeeUnit::Wei, Unit::Usd, Some(2000.0)).unwrap(), "$0.4154 USD");
+/// ```
+pub fn format_amount(
+    amount: U256,
+    from: FeeUnit,
+    to: Unit,
+    token_price: Option<f32>,
+) -> Result<String> {
+    match to.fee_unit() {
+        None => match token_price {
+            Some(_) => Ok(format_dollar_cost_at(amount, from, token_price)),
+            None => Err(eyre::eyre!(
+                "Cannot convert to USD without a {} price",
+                from.token_symbol()
+            )),
+        },
+        Some(fee_unit) if fee_unit != from => Err(eyre::eyre!(
+            "Cannot convert {} to {}",
+            from.name(),
+            to.symbol()
+        )),
+        Some(_) => match to.decimals() {
+            0 => Ok(format!("{amount} {}", to.symbol())),
+            decimals => Ok(format!(
+                "{} {}",
+                utils::format_units(amount, decimals)?,
+                to.symbol()
+            )),
+        },
+    }
+}
+
+/// Same as [`format_amount`], querying the token price from the price oracle when needed.
+pub fn convert(amount: U256, from: FeeUnit, to: Unit) -> Result<String> {
+    let token_price = match to {
+        Unit::Usd => get_token_price(from),
+        _ => None,
+    };
+    format_amount(amount, from, to, token_price)
+}
+
+/// Parses an amount denominated in `unit`, either a `0x` prefixed integer or a decimal number.
+/// # Returns
+/// The amount in the unit's fee unit, wei or fri.
+/// # Example
+/// ```
+/// use howmuch_rs::units::{parse_amount, Unit};
+/// use ethers::types::U256;
+/// assert_eq!(parse_amount("0xbcea9f8f91dd", Unit::Wei).unwrap(), U256::from(207715885355485u64));
+/// assert_eq!(parse_amount("1.5", Unit::Gwei).unwrap(), U256::from(1500000000u64));
+/// ```
+pub fn parse_amount(amount: &str, unit: Unit) -> Result<U256> {
+    if unit.fee_unit().is_none() {
+        return Err(eyre::eyre!("Cannot parse an amount in {}", unit.symbol()));
+    }
+    let scale = U256::exp10(unit.decimals() as usize);
+    match amount.strip_prefix("0x") {
+        Some(_) => U256::from_str_radix(amount, 16)?
+            .checked_mul(scale)
+            .ok_or_else(|| eyre::eyre!("Amount {} overflows", amount)),
+        None => Ok(utils::parse_units(amount, unit.decimals())?.into()),
+    }
+}
@@ -0,0 +1,86 @@
+//! Runs the library against the mock feeder gateway, serving the fixtures of this directory.
+use howmuch_rs::{
+    accounting::query_account_fee_history, currencies, estimate_cost, model::FeeUnit,
+    reconcile::get_fee_reconciliation, testing::MockGateway, EstimateOptions,
+};
+
+const TX_HASH: &str = "0x2d98bda2ac11676c8bbf75d7245de850a7ba634a894ea322656a62ffc64b858";
+const ACCOUNT: &str = "0x77d5345916a48b88ad002ead40344edbaa3a900d66c40d0b8e0c4c9f529d9a6";
+
+#[test]
+fn estimate_on_mocked_networks() {
+    let source = MockGateway::new()
+        .with_receipt_file("tests/example.json")
+        .unwrap()
+        .with_block_file("tests/example_block.json")
+        .unwrap()
+        .start()
+        .unwrap();
+    let destination = MockGateway::new()
+        .with_gas_price(15925, 10_000_000_000u64.into())
+        .with_token_price("ethereum", 2000.0)
+        .start()
+        .unwrap();
+    // The only test setting the price oracle, which is process wide.
+    currencies::set_price_oracle_url(&destination.price_oracle_url());
+    let options = EstimateOptions {
+        usd: Some(true),
+        ..Default::default()
+    };
+    let fees = estimate_cost(
+        Some(TX_HASH),
+        &source.gateway_url(),
+        &destination.gateway_url(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(fees, "0.000339950000000000 ETH ($0.6799 USD)");
+}
+
+#[test]
+fn reconcile_on_mocked_network() {
+    let network = MockGateway::new()
+        .with_block_file("tests/example_block.json")
+        .unwrap()
+        .start()
+        .unwrap();
+    let reconciliation = get_fee_reconciliation(
+        None,
+        &network.gateway_url(),
+        Some("tests/example.json"),
+        None,
+    )
+    .unwrap();
+    assert_eq!(reconciliation.fee_unit, FeeUnit::Wei);
+    assert_eq!(reconciliation.payer(), Some(ACCOUNT));
+}
+
+#[test]
+fn account_history_on_mocked_network() {
+    let receipt = std::fs::read_to_string("tests/example_v3.json").unwrap();
+    let receipt = json::parse(&receipt).unwrap();
+    let block = json::object! {
+        block_hash: "0x1",
+        block_number: 634000,
+        status: "ACCEPTED_ON_L2",
+        timestamp: 1_700_000_000,
+        transactions: [{
+            type: "INVOKE_FUNCTION",
+            version: "0x3",
+            transaction_hash: receipt["transaction_hash"].clone(),
+            sender_address: ACCOUNT,
+            calldata: [],
+        }],
+        transaction_receipts: [receipt.clone()],
+    };
+    let network = MockGateway::new()
+        .with_block(&block.dump())
+        .unwrap()
+        .start()
+        .unwrap();
+    let history =
+        query_account_fee_history(ACCOUNT, 634000, 634000, &network.gateway_url(), false).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].fee_unit, FeeUnit::Fri);
+    assert_eq!(history[0].fee_in_token().unwrap(), "0.010000000000000000");
+}